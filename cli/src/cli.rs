@@ -9,9 +9,17 @@ use maestro::service::settings;
 #[command(author, version, about, long_about = None)]
 pub struct Args {
     /// Device to use (search for compatible device if unspecified)
-    #[arg(short, long, global=true)]
+    #[arg(short, long, visible_alias="address", global=true)]
     pub device: Option<Address>,
 
+    /// Maestro RPC channel to use (discover channel if unspecified)
+    #[arg(short, long, global=true)]
+    pub channel: Option<u32>,
+
+    /// Print output as JSON, one value per line
+    #[arg(long, global=true)]
+    pub json: bool,
+
     #[command(subcommand)]
     pub command: Command
 }
@@ -35,6 +43,9 @@ pub enum Command {
         #[command(subcommand)]
         setting: SetSetting
     },
+
+    /// Listen to setting changes and other device events until interrupted
+    Listen,
 }
 
 #[derive(Debug, Subcommand)]
@@ -0,0 +1,69 @@
+//! Process exit codes, following the conventions of `sysexits.h`.
+
+use maestro::pwrpc::{Error, Status};
+
+
+pub const OK: u8 = 0;
+
+/// The command was used incorrectly.
+pub const USAGE: u8 = 64;
+
+/// The input data was incorrect.
+pub const DATAERR: u8 = 65;
+
+/// The device or a service on it is unavailable.
+pub const UNAVAILABLE: u8 = 69;
+
+/// An internal software error has been detected.
+pub const SOFTWARE: u8 = 70;
+
+/// An error occurred while doing I/O.
+pub const IOERR: u8 = 74;
+
+/// Temporary failure, the user is invited to retry.
+pub const TEMPFAIL: u8 = 75;
+
+/// Insufficient permission to perform the operation.
+pub const NOPERM: u8 = 77;
+
+
+/// Return the exit code for the given error.
+pub fn code_for(err: &anyhow::Error) -> u8 {
+    for cause in err.chain() {
+        if let Some(err) = cause.downcast_ref::<Error>() {
+            return code_for_status(err.code());
+        }
+
+        if cause.is::<bluer::Error>() {
+            return UNAVAILABLE;
+        }
+
+        if cause.is::<std::io::Error>() {
+            return IOERR;
+        }
+    }
+
+    SOFTWARE
+}
+
+fn code_for_status(status: Status) -> u8 {
+    match status {
+        Status::InvalidArgument
+        | Status::OutOfRange => DATAERR,
+        Status::NotFound
+        | Status::FailedPrecondition
+        | Status::Unimplemented
+        | Status::Unavailable => UNAVAILABLE,
+        Status::Cancelled
+        | Status::DeadlineExceeded
+        | Status::Aborted
+        | Status::ResourceExhausted => TEMPFAIL,
+        Status::PermissionDenied
+        | Status::Unauthenticated => NOPERM,
+        Status::DataLoss => IOERR,
+        Status::Ok
+        | Status::Unknown
+        | Status::AlreadyExists
+        | Status::Internal => SOFTWARE,
+    }
+}
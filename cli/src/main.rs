@@ -1,25 +1,45 @@
 mod bt;
 mod cli;
+mod exit;
+
+use std::process::ExitCode;
+use std::time::Duration;
 
 use anyhow::Result;
 use clap::{Parser, CommandFactory};
-use futures::{Future, StreamExt};
+use futures::{Future, TryStreamExt};
 
 use maestro::protocol::{utils, addr};
 use maestro::pwrpc::client::{Client, ClientHandle};
 use maestro::protocol::codec::Codec;
-use maestro::service::MaestroService;
+use maestro::service::{MaestroEvent, MaestroService};
 use maestro::service::settings::{self, Setting, SettingValue};
 
 use cli::*;
 
 
 #[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     tracing_subscriber::fmt::init();
 
-    let args = Args::parse();
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(err) => {
+            let _ = err.print();
+            return ExitCode::from(if err.use_stderr() { exit::USAGE } else { exit::OK });
+        },
+    };
+
+    match run_command(args).await {
+        Ok(()) => ExitCode::from(exit::OK),
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(exit::code_for(&err))
+        },
+    }
+}
 
+async fn run_command(args: Args) -> Result<()> {
     // set up session
     let session = bluer::Session::new().await?;
     let adapter = session.default_adapter().await?;
@@ -44,55 +64,69 @@ async fn main() -> Result<()> {
     let mut client = Client::new(stream);
     let handle = client.handle();
 
-    // resolve channel
-    let channel = utils::resolve_channel(&mut client).await?;
+    // discover channel, unless specified
+    let channel = match args.channel {
+        Some(channel) => {
+            tracing::debug!("using provided channel: {}", channel);
+            channel
+        },
+        None => tokio::select! {
+            res = client.run() => {
+                res?;
+                anyhow::bail!("client terminated unexpectedly");
+            },
+            res = utils::discover_channel(&handle, Duration::from_secs(1)) => res?,
+        },
+    };
+
+    let json = args.json;
 
     match args.command {
         Command::Show { command } => match command {
-            ShowCommand::Software => run(client, cmd_show_software(handle, channel)).await,
-            ShowCommand::Hardware => run(client, cmd_show_hardware(handle, channel)).await,
-            ShowCommand::Runtime => run(client, cmd_show_runtime(handle, channel)).await,
-            ShowCommand::Battery => run(client, cmd_show_battery(handle, channel)).await,
+            ShowCommand::Software => run(client, cmd_show_software(handle, channel, json)).await,
+            ShowCommand::Hardware => run(client, cmd_show_hardware(handle, channel, json)).await,
+            ShowCommand::Runtime => run(client, cmd_show_runtime(handle, channel, json)).await,
+            ShowCommand::Battery => run(client, cmd_show_battery(handle, channel, json)).await,
         },
         Command::Get { setting } => match setting {
             GetSetting::AutoOta => {
-                run(client, cmd_get_setting(handle, channel, settings::id::AutoOtaEnable)).await
+                run(client, cmd_get_setting(handle, channel, json, settings::id::AutoOtaEnable)).await
             },
             GetSetting::Ohd => {
-                run(client, cmd_get_setting(handle, channel, settings::id::OhdEnable)).await
+                run(client, cmd_get_setting(handle, channel, json, settings::id::OhdEnable)).await
             },
             GetSetting::OobeIsFinished => {
-                run(client, cmd_get_setting(handle, channel, settings::id::OobeIsFinished)).await
+                run(client, cmd_get_setting(handle, channel, json, settings::id::OobeIsFinished)).await
             },
             GetSetting::Gestures => {
-                run(client, cmd_get_setting(handle, channel, settings::id::GestureEnable)).await
+                run(client, cmd_get_setting(handle, channel, json, settings::id::GestureEnable)).await
             },
             GetSetting::Diagnostics => {
-                run(client, cmd_get_setting(handle, channel, settings::id::DiagnosticsEnable)).await
+                run(client, cmd_get_setting(handle, channel, json, settings::id::DiagnosticsEnable)).await
             }
             GetSetting::OobeMode => {
-                run(client, cmd_get_setting(handle, channel, settings::id::OobeMode)).await
+                run(client, cmd_get_setting(handle, channel, json, settings::id::OobeMode)).await
             },
             GetSetting::GestureControl => {
-                run(client, cmd_get_setting(handle, channel, settings::id::GestureControl)).await
+                run(client, cmd_get_setting(handle, channel, json, settings::id::GestureControl)).await
             },
             GetSetting::Multipoint => {
-                run(client, cmd_get_setting(handle, channel, settings::id::MultipointEnable)).await
+                run(client, cmd_get_setting(handle, channel, json, settings::id::MultipointEnable)).await
             },
             GetSetting::AncGestureLoop => {
-                run(client, cmd_get_setting(handle, channel, settings::id::AncrGestureLoop)).await
+                run(client, cmd_get_setting(handle, channel, json, settings::id::AncrGestureLoop)).await
             }
             GetSetting::Anc => {
-                run(client, cmd_get_setting(handle, channel, settings::id::CurrentAncrState)).await
+                run(client, cmd_get_setting(handle, channel, json, settings::id::CurrentAncrState)).await
             },
             GetSetting::VolumeEq => {
-                run(client, cmd_get_setting(handle, channel, settings::id::VolumeEqEnable)).await
+                run(client, cmd_get_setting(handle, channel, json, settings::id::VolumeEqEnable)).await
             },
             GetSetting::Eq => {
-                run(client, cmd_get_setting(handle, channel, settings::id::CurrentUserEq)).await
+                run(client, cmd_get_setting(handle, channel, json, settings::id::CurrentUserEq)).await
             },
             GetSetting::Balance => {
-                run(client, cmd_get_setting(handle, channel, settings::id::VolumeAsymmetry)).await
+                run(client, cmd_get_setting(handle, channel, json, settings::id::VolumeAsymmetry)).await
             },
         },
         Command::Set { setting } => match setting {
@@ -140,7 +174,8 @@ async fn main() -> Result<()> {
                         ErrorKind::InvalidValue,
                         "This command requires at least tow enabled ('true') modes"
                     );
-                    err.exit();
+                    let _ = err.print();
+                    std::process::exit(exit::USAGE.into());
                 }
 
                 let value = SettingValue::AncrGestureLoop(value);
@@ -165,14 +200,20 @@ async fn main() -> Result<()> {
                 run(client, cmd_set_setting(handle, channel, value)).await
             }
         },
+        Command::Listen => run(client, cmd_listen(handle, channel, json)).await,
     }
 }
 
-async fn cmd_show_software(handle: ClientHandle, channel: u32) -> Result<()> {
+async fn cmd_show_software(handle: ClientHandle, channel: u32, json: bool) -> Result<()> {
     let mut service = MaestroService::new(handle, channel);
 
     let info = service.get_software_info().await?;
 
+    if json {
+        print_json(&info)?;
+        return Ok(());
+    }
+
     let fw_ver_case = info.firmware.as_ref()
         .and_then(|fw| fw.case.as_ref())
         .map(|fw| fw.version_string.as_str())
@@ -211,11 +252,16 @@ async fn cmd_show_software(handle: ClientHandle, channel: u32) -> Result<()> {
     Ok(())
 }
 
-async fn cmd_show_hardware(handle: ClientHandle, channel: u32) -> Result<()> {
+async fn cmd_show_hardware(handle: ClientHandle, channel: u32, json: bool) -> Result<()> {
     let mut service = MaestroService::new(handle, channel);
 
     let info = service.get_hardware_info().await?;
 
+    if json {
+        print_json(&info)?;
+        return Ok(());
+    }
+
     let serial_case = info.serial_number.as_ref()
         .map(|ser| ser.case.as_str())
         .unwrap_or("unknown");
@@ -236,13 +282,15 @@ async fn cmd_show_hardware(handle: ClientHandle, channel: u32) -> Result<()> {
     Ok(())
 }
 
-async fn cmd_show_runtime(handle: ClientHandle, channel: u32) -> Result<()> {
+async fn cmd_show_runtime(handle: ClientHandle, channel: u32, json: bool) -> Result<()> {
     let mut service = MaestroService::new(handle, channel);
 
-    let mut call = service.subscribe_to_runtime_info()?;
+    let info = service.get_runtime_info().await?;
 
-    let info = call.stream().next().await
-        .ok_or_else(|| anyhow::anyhow!("stream terminated without item"))??;
+    if json {
+        print_json(&info)?;
+        return Ok(());
+    }
 
     let bat_level_case = info.battery_info.as_ref()
         .and_then(|b| b.case.as_ref())
@@ -324,38 +372,34 @@ async fn cmd_show_runtime(handle: ClientHandle, channel: u32) -> Result<()> {
     Ok(())
 }
 
-async fn cmd_show_battery(handle: ClientHandle, channel: u32) -> Result<()> {
+async fn cmd_show_battery(handle: ClientHandle, channel: u32, json: bool) -> Result<()> {
     let mut service = MaestroService::new(handle, channel);
 
-    let mut call = service.subscribe_to_runtime_info()?;
+    let info = service.get_battery_info().await?;
 
-    let info = call.stream().next().await
-        .ok_or_else(|| anyhow::anyhow!("stream terminated without item"))??;
+    if json {
+        print_json(&info)?;
+        return Ok(());
+    }
 
-    let bat_level_case = info.battery_info.as_ref()
-        .and_then(|b| b.case.as_ref())
+    let bat_level_case = info.case.as_ref()
         .map(|b| b.level);
 
-    let bat_state_case = info.battery_info.as_ref()
-        .and_then(|b| b.case.as_ref())
+    let bat_state_case = info.case.as_ref()
         .map(|b| if b.state == 2 { "charging" } else if b.state == 1 { "not charging" } else { "unknown" })
         .unwrap_or("unknown");
 
-    let bat_level_left = info.battery_info.as_ref()
-        .and_then(|b| b.left.as_ref())
+    let bat_level_left = info.left.as_ref()
         .map(|b| b.level);
 
-    let bat_state_left = info.battery_info.as_ref()
-        .and_then(|b| b.left.as_ref())
+    let bat_state_left = info.left.as_ref()
         .map(|b| if b.state == 2 { "charging" } else if b.state == 1 { "not charging" } else { "unknown" })
         .unwrap_or("unknown");
 
-    let bat_level_right = info.battery_info.as_ref()
-        .and_then(|b| b.right.as_ref())
+    let bat_level_right = info.right.as_ref()
         .map(|b| b.level);
 
-    let bat_state_right = info.battery_info.as_ref()
-        .and_then(|b| b.right.as_ref())
+    let bat_state_right = info.right.as_ref()
         .map(|b| if b.state == 2 { "charging" } else if b.state == 1 { "not charging" } else { "unknown" })
         .unwrap_or("unknown");
 
@@ -378,15 +422,20 @@ async fn cmd_show_battery(handle: ClientHandle, channel: u32) -> Result<()> {
     Ok(())
 }
 
-async fn cmd_get_setting<T>(handle: ClientHandle, channel: u32, setting: T) -> Result<()>
+async fn cmd_get_setting<T>(handle: ClientHandle, channel: u32, json: bool, setting: T) -> Result<()>
 where
     T: Setting,
-    T::Type: std::fmt::Display,
+    T::Type: std::fmt::Display + serde::Serialize,
 {
     let mut service = MaestroService::new(handle, channel);
 
     let value = service.read_setting(setting).await?;
-    println!("{}", value);
+
+    if json {
+        print_json(&value)?;
+    } else {
+        println!("{}", value);
+    }
 
     Ok(())
 }
@@ -398,6 +447,33 @@ async fn cmd_set_setting(handle: ClientHandle, channel: u32, setting: SettingVal
     Ok(())
 }
 
+async fn cmd_listen(handle: ClientHandle, channel: u32, json: bool) -> Result<()> {
+    let mut service = MaestroService::new(handle, channel);
+
+    let mut events = service.subscribe_to_all_events()?;
+
+    while let Some(event) = events.try_next().await? {
+        if json {
+            print_json(&event)?;
+            continue;
+        }
+
+        match event {
+            MaestroEvent::Settings(rsp) => println!("{}", rsp),
+            MaestroEvent::RuntimeInfo(info) => println!("{}", info),
+            MaestroEvent::OobeAction(rsp) => println!("{}", rsp),
+            event => println!("{:?}", event),
+        }
+    }
+
+    Ok(())
+}
+
+fn print_json(value: &impl serde::Serialize) -> Result<()> {
+    println!("{}", maestro::protocol::types::to_json(value)?);
+    Ok(())
+}
+
 pub async fn run<S, E, F>(mut client: Client<S>, task: F) -> Result<()>
 where
     S: futures::Sink<maestro::pwrpc::types::RpcPacket>,
@@ -421,7 +497,7 @@ where
         },
     }
 
-    client.terminate().await?;
+    client.graceful_shutdown(Duration::from_secs(2)).await?;
 
     tracing::trace!("client terminated successfully");
     Ok(())
@@ -0,0 +1,62 @@
+//! Fuzz target for decoding untrusted data received from the device.
+//!
+//! Feeds arbitrary bytes into the codec, which must never panic but only
+//! return errors, and checks that all packets decoded from it as well as a
+//! packet carrying the input as payload round-trip through encoding.
+//!
+//! Usage:
+//!   cargo +nightly fuzz run codec_decode
+
+#![no_main]
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use tokio_util::codec::{Decoder, Encoder};
+
+use maestro::hdlc::RecoveryPolicy;
+use maestro::protocol::codec::Codec;
+use maestro::pwrpc::types::{PacketType, RpcPacket};
+
+
+fuzz_target!(|data: &[u8]| {
+    for policy in [RecoveryPolicy::SkipToNextFrame, RecoveryPolicy::Fail] {
+        let mut codec = Codec::new().with_error_recovery(policy);
+        let mut buf = BytesMut::from(data);
+
+        while let Ok(Some(packet)) = codec.decode(&mut buf) {
+            roundtrip(&packet);
+        }
+    }
+
+    let packet = RpcPacket {
+        r#type: PacketType::ServerStream.into(),
+        channel_id: 18,
+        service_id: 0x7ede71ea,
+        method_id: 0x7199fa44,
+        payload: data.to_vec().into(),
+        status: 0,
+        call_id: data.len() as u32,
+    };
+
+    roundtrip(&packet);
+});
+
+fn roundtrip(packet: &RpcPacket) {
+    let mut encoder = Codec::new().with_max_payload_size(usize::MAX);
+    let mut buf = BytesMut::new();
+
+    // packets on unknown channels cannot be encoded, but must not panic
+    if encoder.encode(packet, &mut buf).is_err() {
+        return;
+    }
+
+    // the escaped frame is never smaller than the unescaped one
+    let mut decoder = Codec::with_max_frame_size(buf.len());
+
+    let decoded = decoder.decode(&mut buf)
+        .expect("failed to decode encoded packet")
+        .expect("encoded packet is incomplete");
+
+    assert_eq!(&decoded, packet);
+    assert!(buf.is_empty());
+}
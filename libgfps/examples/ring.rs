@@ -3,21 +3,67 @@
 //! WARNING: DO NOT RUN THIS EXAMPLE WITH THE BUDS IN YOUR EAR! YOU HAVE BEEN WARNED.
 //!
 //! Usage:
-//!   cargo run --example ring -- <bluetooth-device-address>
+//!   cargo run --example ring -- <bluetooth-device-address> [<warning-window-secs>]
+//!
+//! A warning is printed if the buds are still ringing after the given window
+//! (default: 15s). Ringing is stopped after 30s regardless.
 
 use std::str::FromStr;
+use std::time::Duration;
 
 use bluer::{Address, Session, Device};
 use bluer::rfcomm::{Profile, Role, ProfileHandle, ReqError};
 
 use futures::{StreamExt, SinkExt};
 
-use gfps::msg::{Codec, Message, EventGroup, DeviceActionEventCode, AcknowledgementEventCode};
+use gfps::msg::{Codec, Message, EventGroup, DeviceActionEventCode, AcknowledgementEventCode, RingState};
 
 use num_enum::FromPrimitive;
 
 use smallvec::smallvec;
 
+use tokio::time::Instant;
+
+
+/// Warns if ringing has been started but not stopped within a given window.
+///
+/// The buds do not stop ringing on their own. Stopping them is still up to
+/// the caller, this only reports ringing that has likely been forgotten.
+struct RingGuard {
+    window: Duration,
+    deadline: Option<Instant>,
+}
+
+impl RingGuard {
+    fn new(window: Duration) -> Self {
+        Self { window, deadline: None }
+    }
+
+    /// Update the guard with the ringing state sent to or received from the
+    /// buds. The window starts once they are ringing.
+    fn update(&mut self, state: RingState) {
+        if !state.is_ringing() {
+            self.deadline = None;
+        } else if self.deadline.is_none() {
+            self.deadline = Some(Instant::now() + self.window);
+        }
+    }
+
+    /// Wait for the window of the given deadline to pass. Never completes if
+    /// there is no deadline, i.e., if the buds are not ringing.
+    async fn expired(deadline: Option<Instant>) {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => futures::future::pending().await,
+        }
+    }
+
+    /// Print a warning. Only warns once per ringing period.
+    fn warn(&mut self) {
+        println!("WARNING: buds still ringing after {:?}, ringing needs to be stopped explicitly", self.window);
+        self.deadline = None;
+    }
+}
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> bluer::Result<()> {
@@ -25,6 +71,13 @@ async fn main() -> bluer::Result<()> {
     let addr = std::env::args().nth(1).expect("need device address as argument");
     let addr = Address::from_str(&addr)?;
 
+    let window = std::env::args().nth(2)
+        .map(|secs| secs.parse().expect("invalid warning window"))
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(15));
+
+    let mut guard = RingGuard::new(window);
+
     // set up session
     let session = Session::new().await?;
     let adapter = session.default_adapter().await?;
@@ -55,17 +108,11 @@ async fn main() -> bluer::Result<()> {
     let mut stream = codec.wrap(stream);
 
     // send "ring" message
-    //
-    // Note: Pixel Buds Pro ignore messages with a timeout. So don't specify
-    // one here.
-    let msg = Message {
-        group: EventGroup::DeviceAction.into(),
-        code: DeviceActionEventCode::Ring.into(),
-        data: smallvec![0x03],      // 0b01: right, 0b10: left, 0b10|0b01 = 0b11: both
-    };
+    let state = RingState { left: true, right: true };
 
     println!("Ringing buds...");
-    stream.send(&msg).await?;
+    stream.send(&state.to_message()).await?;
+    guard.update(state);
 
     // An ACK message should come in 1s. Wait for that.
     let timeout = tokio::time::Instant::now() + tokio::time::Duration::from_secs(1);
@@ -159,23 +206,24 @@ async fn main() -> bluer::Result<()> {
 
                         stream.send(&ack).await?;
 
-                        let status = msg.data[0];
+                        let status = RingState::from_byte(msg.data[0]);
+                        guard.update(status);
 
                         println!("Received ring update:");
 
-                        if status & 0b01 != 0 {
+                        if status.right {
                             println!("  right: ringing");
                         } else {
                             println!("  right: not ringing");
                         }
 
-                        if status & 0b10 != 0 {
+                        if status.left {
                             println!("  left:  ringing");
                         } else {
                             println!("  left:  not ringing");
                         }
 
-                        if status & 0b11 == 0 {
+                        if !status.is_ringing() {
                             println!("Buds stopped ringing, exiting...");
                             return Ok(());
                         }
@@ -193,17 +241,17 @@ async fn main() -> bluer::Result<()> {
                     }
                 }
             },
+            _ = RingGuard::expired(guard.deadline) => {
+                guard.warn();
+            },
             _ = tokio::time::sleep_until(timeout) => {
                 println!("Sending command to stop ringing...");
 
                 // send message to stop ringing
-                let msg = Message {
-                    group: EventGroup::DeviceAction.into(),
-                    code: DeviceActionEventCode::Ring.into(),
-                    data: smallvec![0x00],
-                };
+                let state = RingState::default();
 
-                stream.send(&msg).await?;
+                stream.send(&state.to_message()).await?;
+                guard.update(state);
 
                 timeout = tokio::time::Instant::now() + tokio::time::Duration::from_secs(10);
             },
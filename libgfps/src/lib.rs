@@ -2,5 +2,9 @@
 //! communication via the dedicated GFPS RFCOMM channel.
 //!
 //! See <https://developers.google.com/nearby/fast-pair> for the specification.
+//!
+//! Enums for event groups and codes are marked as `#[non_exhaustive]`, with
+//! values not known to this library mapped to an `Unknown` variant carrying
+//! the raw value.
 
 pub mod msg;
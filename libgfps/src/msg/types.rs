@@ -146,8 +146,9 @@ pub enum PlatformType {
 }
 
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum BatteryInfo {
+    #[default]
     Unknown,
     Known {
         is_charging: bool,
@@ -176,12 +177,6 @@ impl BatteryInfo {
     }
 }
 
-impl Default for BatteryInfo {
-    fn default() -> Self {
-        BatteryInfo::Unknown
-    }
-}
-
 impl Display for BatteryInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -197,3 +192,80 @@ impl Display for BatteryInfo {
         }
     }
 }
+
+
+/// Ringing state of the buds, used as data for `DeviceActionEventCode::Ring`
+/// messages.
+///
+/// Sent to the device to start or stop ringing, and received from the device
+/// as status update, e.g. once ringing has been stopped by touching the buds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RingState {
+    pub left: bool,
+    pub right: bool,
+}
+
+impl RingState {
+    pub fn from_byte(value: u8) -> Self {
+        RingState {
+            left: (value & 0b10) != 0,
+            right: (value & 0b01) != 0,
+        }
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        ((self.left as u8) << 1) | (self.right as u8)
+    }
+
+    pub fn is_ringing(&self) -> bool {
+        self.left || self.right
+    }
+
+    /// Create a message to set the ringing state of the buds.
+    ///
+    /// Note that Pixel Buds Pro ignore ring messages with a timeout, so none
+    /// is specified. Ringing needs to be stopped explicitly by sending a
+    /// message with both buds disabled. The `ring` example shows how to warn
+    /// about ringing that has not been stopped within some time.
+    pub fn to_message(&self) -> Message {
+        Message {
+            group: EventGroup::DeviceAction.into(),
+            code: DeviceActionEventCode::Ring.into(),
+            data: smallvec::smallvec![self.to_byte()],
+        }
+    }
+}
+
+impl Display for RingState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.left, self.right) {
+            (true, true) => write!(f, "both ringing"),
+            (true, false) => write!(f, "left ringing"),
+            (false, true) => write!(f, "right ringing"),
+            (false, false) => write!(f, "not ringing"),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ring_state() {
+        for value in 0b00..=0b11 {
+            assert_eq!(RingState::from_byte(value).to_byte(), value);
+        }
+
+        assert_eq!(RingState::from_byte(0b00), RingState { left: false, right: false });
+        assert_eq!(RingState::from_byte(0b01), RingState { left: false, right: true });
+        assert_eq!(RingState::from_byte(0b10), RingState { left: true, right: false });
+        assert_eq!(RingState::from_byte(0b11), RingState { left: true, right: true });
+
+        let msg = RingState { left: true, right: false }.to_message();
+        assert_eq!(msg.group, u8::from(EventGroup::DeviceAction));
+        assert_eq!(msg.code, u8::from(DeviceActionEventCode::Ring));
+        assert_eq!(&msg.data[..], &[0b10]);
+    }
+}
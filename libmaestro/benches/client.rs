@@ -0,0 +1,85 @@
+//! Benchmarks for unary call round-trips via the RPC client.
+//!
+//! The client is connected to an in-memory device echoing each request, so
+//! this measures the overhead of the client itself, without any framing.
+//!
+//! Run via `cargo bench -p maestro --bench client`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use maestro::pwrpc::client::{Client, ClientHandle, UnaryResponse};
+use maestro::pwrpc::testing;
+
+
+const PAYLOAD_SIZES: [usize; 3] = [32, 256, 4096];
+const CONCURRENCY: [usize; 3] = [1, 4, 16];
+
+
+/// Start the given number of calls at the same time and wait for all of them.
+async fn round_trips(handle: &mut ClientHandle, payload: &[u8], concurrency: usize) {
+    let calls: Vec<UnaryResponse<Vec<u8>>> = (0..concurrency)
+        .map(|_| {
+            let request = handle.request(payload.to_vec())
+                .channel_id(18)
+                .service_id(0x01)
+                .method_id(0x02)
+                .build()
+                .unwrap();
+
+            handle.call_unary(request).unwrap()
+        })
+        .collect();
+
+    for mut call in calls {
+        let response = call.result().await.unwrap();
+        assert_eq!(response.len(), payload.len());
+    }
+}
+
+fn bench_unary(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    let (stream, mut device) = testing::pair();
+
+    let mut client = Client::new(stream);
+    let mut handle = client.handle();
+
+    rt.spawn(async move { client.run().await });
+
+    // echo the payload of each request
+    rt.spawn(async move {
+        while let Some(request) = device.recv().await {
+            let mut response = testing::response(&request, &());
+            response.payload = request.payload;
+
+            if device.send(response).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut group = c.benchmark_group("unary");
+
+    for payload_size in PAYLOAD_SIZES {
+        let payload = vec![0x5a; payload_size];
+
+        for concurrency in CONCURRENCY {
+            group.throughput(Throughput::Elements(concurrency as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("payload-{payload_size}"), concurrency),
+                &concurrency,
+                |b, &concurrency| {
+                    b.iter(|| rt.block_on(round_trips(&mut handle, &payload, concurrency)))
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+
+criterion_group!(benches, bench_unary);
+criterion_main!(benches);
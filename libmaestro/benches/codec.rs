@@ -0,0 +1,70 @@
+//! Benchmarks for decoding packets via the Maestro codec.
+//!
+//! Run via `cargo bench -p maestro --bench codec`.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use maestro::protocol::codec::{Codec, DEFAULT_MAX_PAYLOAD_SIZE};
+use maestro::pwrpc::types::{PacketType, RpcPacket};
+
+
+const PAYLOAD_SIZES: [usize; 3] = [32, 256, DEFAULT_MAX_PAYLOAD_SIZE];
+const BATCH_SIZES: [usize; 3] = [1, 16, 128];
+
+
+fn packet(payload_size: usize, call_id: u32) -> RpcPacket {
+    RpcPacket::builder()
+        .packet_type(PacketType::ServerStream)
+        .channel_id(18)
+        .service_id(0x7ede71ea)
+        .method_id(0x2d3a0e6e)
+        .call_id(call_id)
+        // include flag and escape bytes so that escaping is exercised
+        .payload((0..payload_size).map(|i| i as u8 ^ 0x7e).collect::<Vec<_>>())
+        .build()
+        .unwrap()
+}
+
+/// Encode a batch of packets into a single buffer, as received from the
+/// transport.
+fn encode_batch(payload_size: usize, batch_size: usize) -> Vec<u8> {
+    let mut codec = Codec::new();
+
+    (0..batch_size as u32)
+        .flat_map(|call_id| codec.encode_to_vec(&packet(payload_size, call_id)).unwrap())
+        .collect()
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_from_slice");
+
+    for payload_size in PAYLOAD_SIZES {
+        for batch_size in BATCH_SIZES {
+            let data = encode_batch(payload_size, batch_size);
+
+            group.throughput(Throughput::Bytes(data.len() as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("payload-{payload_size}"), batch_size),
+                &data,
+                |b, data| {
+                    b.iter(|| {
+                        let packets = Codec::new()
+                            .decode_from_slice(black_box(data))
+                            .unwrap();
+
+                        assert_eq!(packets.len(), batch_size);
+                        packets
+                    })
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
@@ -1,7 +1,30 @@
 use std::io::Result;
 
 fn main() -> Result<()> {
-    prost_build::compile_protos(&["proto/pw.rpc.packet.proto"], &["proto/"])?;
-    prost_build::compile_protos(&["proto/maestro_pw.proto"], &["proto/"])?;
+    prost_build::Config::new()
+        .bytes([".pw.rpc.packet.RpcPacket.payload"])
+        .type_attribute(
+            ".pw.rpc.packet.RpcPacket",
+            "#[cfg_attr(feature = \"serde\", derive(serde::Serialize, serde::Deserialize))]",
+        )
+        .type_attribute(
+            ".pw.rpc.packet.PacketType",
+            "#[cfg_attr(feature = \"serde\", derive(serde::Serialize, serde::Deserialize))]",
+        )
+        .field_attribute(
+            ".pw.rpc.packet.RpcPacket.type",
+            "#[cfg_attr(feature = \"serde\", serde(with = \"crate::pwrpc::serde_fields::packet_type\"))]",
+        )
+        .field_attribute(
+            ".pw.rpc.packet.RpcPacket.payload",
+            "#[cfg_attr(feature = \"serde\", serde(with = \"crate::pwrpc::serde_fields::base64\"))]",
+        )
+        .compile_protos(&["proto/pw.rpc.packet.proto"], &["proto/"])?;
+    prost_build::Config::new()
+        .type_attribute(
+            ".maestro_pw",
+            "#[cfg_attr(feature = \"serde\", derive(serde::Serialize))]",
+        )
+        .compile_protos(&["proto/maestro_pw.proto"], &["proto/"])?;
     Ok(())
 }
@@ -0,0 +1,102 @@
+//! Example for periodically polling battery info via the Maestro service.
+//!
+//! Usage:
+//!   cargo run --example maestro_battery -- <bluetooth-device-address> [interval-in-seconds]
+
+mod common;
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::bail;
+use bluer::{Address, Session};
+
+use maestro::connection::{self, RetryOptions};
+use maestro::protocol::codec::Codec;
+use maestro::protocol::types::{BatteryState, DeviceBatteryInfo};
+use maestro::pwrpc::client::{Client, ClientHandle};
+use maestro::service::MaestroService;
+
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), anyhow::Error> {
+    tracing_subscriber::fmt::init();
+
+    // handle command line arguments
+    let addr = std::env::args().nth(1).expect("need device address as argument");
+    let addr = Address::from_str(&addr)?;
+
+    let interval = match std::env::args().nth(2) {
+        Some(secs) => Duration::from_secs(secs.parse()?),
+        None => Duration::from_secs(30),
+    };
+
+    if interval.is_zero() {
+        bail!("interval must be at least one second");
+    }
+
+    // set up session
+    let session = Session::new().await?;
+    let adapter = session.default_adapter().await?;
+    let dev = adapter.device(addr)?;
+
+    println!("Connecting to Maestro profile of {}", dev.address());
+    let stream = connection::connect_with_retry(&session, &dev, &RetryOptions::default()).await?;
+
+    // set up stream for RPC communication
+    let codec = Codec::new();
+    let stream = codec.wrap(stream);
+
+    // set up RPC client
+    let mut client = Client::new(stream);
+    let handle = client.handle();
+
+    // discover the channel number
+    let channel = common::discover_channel(&mut client).await?;
+
+    let exec_task = common::run_client(client);
+    let poll_task = poll_battery(handle, channel, interval);
+
+    // the client task stops on Ctrl-C, the polling task never stops on its own
+    tokio::select! {
+        res = exec_task => res,
+        res = poll_task => res,
+    }
+}
+
+async fn poll_battery(handle: ClientHandle, channel: u32, interval: Duration) -> anyhow::Result<()> {
+    let mut service = MaestroService::new(handle, channel);
+
+    // the first tick completes immediately
+    let mut interval = tokio::time::interval(interval);
+    let start = tokio::time::Instant::now();
+
+    println!();
+    println!("{:<10} {:>6} {:>6} {:>6}", "elapsed", "case", "left", "right");
+
+    loop {
+        interval.tick().await;
+
+        let battery = service.get_battery_info().await?;
+
+        println!(
+            "{:<10} {:>6} {:>6} {:>6}",
+            format!("{}s", start.elapsed().as_secs()),
+            level(battery.case.as_ref()),
+            level(battery.left.as_ref()),
+            level(battery.right.as_ref()),
+        );
+    }
+}
+
+/// Format battery level, marking charging devices with `+` and printing `--`
+/// if the level is not known, e.g. for buds stored in the closed case.
+fn level(info: Option<&DeviceBatteryInfo>) -> String {
+    match info {
+        Some(info) if info.state == BatteryState::BatteryCharging as i32 => {
+            format!("{}%+", info.level)
+        },
+        Some(info) => format!("{}%", info.level),
+        None => "--".to_owned(),
+    }
+}
@@ -0,0 +1,94 @@
+//! Example for recording a capture of all Maestro packets sent and received.
+//!
+//! Packets are written as newline-delimited JSON, including a timestamp for
+//! each packet. To generate some traffic, this requests software and hardware
+//! information and then listens to all events until interrupted via Ctrl-C.
+//!
+//! Usage:
+//!   cargo run --features serde --example maestro_dump -- <bluetooth-device-address> --output <file>
+
+mod common;
+
+use std::fs::File;
+use std::io::LineWriter;
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use bluer::{Address, Session};
+use futures::StreamExt;
+
+use maestro::connection::{self, RetryOptions};
+use maestro::protocol::codec::Codec;
+use maestro::pwrpc::capture::PacketCapture;
+use maestro::pwrpc::client::{Client, ClientHandle};
+use maestro::service::MaestroService;
+
+
+const USAGE: &str = "usage: maestro_dump <bluetooth-device-address> --output <file>";
+
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), anyhow::Error> {
+    tracing_subscriber::fmt::init();
+
+    // handle command line arguments
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (addr, output) = match args.as_slice() {
+        [addr, flag, output] if flag == "--output" => (addr, output),
+        [flag, output, addr] if flag == "--output" => (addr, output),
+        _ => bail!("invalid arguments\n{USAGE}"),
+    };
+
+    let addr = Address::from_str(addr)
+        .with_context(|| format!("invalid device address '{addr}'\n{USAGE}"))?;
+
+    // flush after each record so that the capture is usable even if we are
+    // terminated unexpectedly
+    let file = File::create(output)
+        .with_context(|| format!("failed to create output file '{output}'"))?;
+    let writer = LineWriter::new(file);
+
+    // set up session
+    let session = Session::new().await?;
+    let adapter = session.default_adapter().await?;
+    let dev = adapter.device(addr)?;
+
+    println!("Connecting to Maestro profile of {}", dev.address());
+    let stream = connection::connect_with_retry(&session, &dev, &RetryOptions::default()).await?;
+
+    // set up stream for RPC communication, capturing all packets
+    let codec = Codec::new();
+    let stream = codec.wrap(stream);
+    let stream = PacketCapture::new(stream, writer);
+
+    // set up RPC client
+    let mut client = Client::new(stream);
+    let handle = client.handle();
+
+    // discover the channel number
+    let channel = common::discover_channel(&mut client).await?;
+
+    println!("Writing capture to '{output}', press Ctrl-C to stop");
+
+    let exec_task = common::run_client(client);
+    let traffic_task = generate_traffic(handle, channel);
+
+    tokio::select! {
+        res = exec_task => res,
+        res = traffic_task => res,
+    }
+}
+
+async fn generate_traffic(handle: ClientHandle, channel: u32) -> anyhow::Result<()> {
+    let mut service = MaestroService::new(handle, channel);
+
+    service.get_software_info().await?;
+    service.get_hardware_info().await?;
+
+    let mut events = service.subscribe_to_all_events()?;
+    while let Some(event) = events.next().await {
+        tracing::debug!("received event: {:?}", event?);
+    }
+
+    Ok(())
+}
@@ -11,9 +11,9 @@ use anyhow::bail;
 use bluer::{Address, Session};
 use futures::StreamExt;
 
+use maestro::connection::{self, RetryOptions};
 use maestro::protocol::codec::Codec;
 use maestro::protocol::types::RuntimeInfo;
-use maestro::protocol::utils;
 use maestro::pwrpc::client::{Client, ClientHandle};
 use maestro::service::MaestroService;
 
@@ -55,7 +55,7 @@ async fn main() -> Result<(), anyhow::Error> {
     println!();
 
     println!("Connecting to Maestro profile");
-    let stream = common::connect_maestro_rfcomm(&session, &dev).await?;
+    let stream = connection::connect_with_retry(&session, &dev, &RetryOptions::default()).await?;
 
     println!("Profile connected");
 
@@ -67,8 +67,8 @@ async fn main() -> Result<(), anyhow::Error> {
     let mut client = Client::new(stream);
     let handle = client.handle();
 
-    // retreive the channel numer
-    let channel = utils::resolve_channel(&mut client).await?;
+    // discover the channel number
+    let channel = common::discover_channel(&mut client).await?;
 
     let exec_task = common::run_client(client);
     let battery_task = get_battery(handle, channel);
@@ -89,31 +89,19 @@ async fn main() -> Result<(), anyhow::Error> {
     println!("Battery status:");
 
     if let Some(info) = info.case {
-        match info.state {
-            1 => println!("  case:  {}% (not charging)", info.level),
-            2 => println!("  case:  {}% (charging)", info.level),
-            x => println!("  case:  {}% (unknown state: {})", info.level, x),
-        }
+        println!("  case:  {}", info);
     } else {
         println!("  case: unknown");
     }
 
     if let Some(info) = info.left {
-        match info.state {
-            1 => println!("  left:  {}% (not charging)", info.level),
-            2 => println!("  left:  {}% (charging)", info.level),
-            x => println!("  left:  {}% (unknown state: {})", info.level, x),
-        }
+        println!("  left:  {}", info);
     } else {
         println!("  left: unknown");
     }
 
     if let Some(info) = info.right {
-        match info.state {
-            1 => println!("  right: {}% (not charging)", info.level),
-            2 => println!("  right: {}% (charging)", info.level),
-            x => println!("  right: {}% (unknown state: {})", info.level, x),
-        }
+        println!("  right: {}", info);
     } else {
         println!("  right: unknown");
     }
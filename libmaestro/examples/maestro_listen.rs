@@ -1,17 +1,22 @@
 //! Simple example for listening to Maestro messages sent via the RFCOMM channel.
 //!
 //! Usage:
-//!   cargo run --example maestro_listen -- <bluetooth-device-address>
+//!   cargo run --example maestro_listen -- <bluetooth-device-address> [--json]
+//!
+//! With `--json`, messages are printed as newline-delimited JSON and all other
+//! output is suppressed. This requires the `serde` feature.
 
 mod common;
 
+use std::fmt::Display;
 use std::str::FromStr;
 
+use anyhow::bail;
 use bluer::{Address, Session};
 use futures::StreamExt;
 
+use maestro::connection::{self, RetryOptions};
 use maestro::protocol::codec::Codec;
-use maestro::protocol::utils;
 use maestro::pwrpc::client::{Client, ClientHandle};
 use maestro::service::MaestroService;
 
@@ -21,43 +26,64 @@ async fn main() -> Result<(), anyhow::Error> {
     tracing_subscriber::fmt::init();
 
     // handle command line arguments
-    let addr = std::env::args().nth(1).expect("need device address as argument");
-    let addr = Address::from_str(&addr)?;
+    let (args, flags): (Vec<String>, Vec<String>) = std::env::args().skip(1)
+        .partition(|arg| !arg.starts_with("--"));
+
+    let json = match flags.as_slice() {
+        [] => false,
+        [flag] if flag == "--json" => true,
+        _ => bail!("unsupported arguments: {:?}", flags),
+    };
+
+    if json && !cfg!(feature = "serde") {
+        bail!("JSON output requires the 'serde' feature");
+    }
+
+    let addr = args.first().expect("need device address as argument");
+    let addr = Address::from_str(addr)?;
 
     // set up session
     let session = Session::new().await?;
     let adapter = session.default_adapter().await?;
 
-    println!("Using adapter '{}'", adapter.name());
-
     // get device
     let dev = adapter.device(addr)?;
-    let uuids = {
-        let mut uuids = Vec::from_iter(dev.uuids().await?
-            .unwrap_or_default()
-            .into_iter());
-
-        uuids.sort_unstable();
-        uuids
-    };
 
-    println!("Found device:");
-    println!("  alias:     {}", dev.alias().await?);
-    println!("  address:   {}", dev.address());
-    println!("  paired:    {}", dev.is_paired().await?);
-    println!("  connected: {}", dev.is_connected().await?);
-    println!("  UUIDs:");
-    for uuid in uuids {
-        println!("    {}", uuid);
+    if !json {
+        println!("Using adapter '{}'", adapter.name());
+
+        let uuids = {
+            let mut uuids = Vec::from_iter(dev.uuids().await?
+                .unwrap_or_default()
+                .into_iter());
+
+            uuids.sort_unstable();
+            uuids
+        };
+
+        println!("Found device:");
+        println!("  alias:     {}", dev.alias().await?);
+        println!("  address:   {}", dev.address());
+        println!("  paired:    {}", dev.is_paired().await?);
+        println!("  connected: {}", dev.is_connected().await?);
+        println!("  UUIDs:");
+        for uuid in uuids {
+            println!("    {}", uuid);
+        }
+        println!();
     }
-    println!();
 
     // try to reconnect if connection is reset
     loop {
-        println!("Connecting to Maestro profile");
-        let stream = common::connect_maestro_rfcomm(&session, &dev).await?;
+        if !json {
+            println!("Connecting to Maestro profile");
+        }
+
+        let stream = connection::connect_with_retry(&session, &dev, &RetryOptions::default()).await?;
 
-        println!("Profile connected");
+        if !json {
+            println!("Profile connected");
+        }
 
         // set up stream for RPC communication
         let codec = Codec::new();
@@ -67,11 +93,11 @@ async fn main() -> Result<(), anyhow::Error> {
         let mut client = Client::new(stream);
         let handle = client.handle();
 
-        // retreive the channel numer
-        let channel = utils::resolve_channel(&mut client).await?;
+        // discover the channel number
+        let channel = common::discover_channel(&mut client).await?;
 
         let exec_task = common::run_client(client);
-        let listen_task = run_listener(handle, channel);
+        let listen_task = run_listener(handle, channel, json);
 
         tokio::select! {
             res = exec_task => {
@@ -89,8 +115,10 @@ async fn main() -> Result<(), anyhow::Error> {
                                 // The Pixel Buds Pro can hand off processing between each
                                 // other. On a switch, the connection is reset. Wait a bit
                                 // and then try to reconnect.
-                                println!();
-                                println!("Connection reset. Attempting to reconnect...");
+                                if !json {
+                                    println!();
+                                    println!("Connection reset. Attempting to reconnect...");
+                                }
                                 tokio::time::sleep(std::time::Duration::from_millis(500)).await;
                                 continue;
                             }
@@ -116,21 +144,25 @@ async fn main() -> Result<(), anyhow::Error> {
     }
 }
 
-async fn run_listener(handle: ClientHandle, channel: u32) -> anyhow::Result<()> {
-    println!("Sending GetSoftwareInfo request");
-    println!();
-
+async fn run_listener(handle: ClientHandle, channel: u32, json: bool) -> anyhow::Result<()> {
     let mut service = MaestroService::new(handle, channel);
 
+    if !json {
+        println!("Sending GetSoftwareInfo request");
+        println!();
+    }
+
     let info = service.get_software_info().await?;
-    println!("{:#?}", info);
+    print_message(&info, json)?;
 
-    println!();
-    println!("Listening to settings changes...");
-    println!();
+    if !json {
+        println!();
+        println!("Listening to settings changes...");
+        println!();
+    }
 
-    let task_rtinfo = run_listener_rtinfo(service.clone());
-    let task_settings = run_listener_settings(service.clone());
+    let task_rtinfo = run_listener_rtinfo(service.clone(), json);
+    let task_settings = run_listener_settings(service.clone(), json);
 
     tokio::select! {
         res = task_rtinfo => res,
@@ -138,20 +170,38 @@ async fn run_listener(handle: ClientHandle, channel: u32) -> anyhow::Result<()>
     }
 }
 
-async fn run_listener_rtinfo(mut service: MaestroService) -> anyhow::Result<()> {
+async fn run_listener_rtinfo(mut service: MaestroService, json: bool) -> anyhow::Result<()> {
     let mut call = service.subscribe_to_runtime_info()?;
     while let Some(msg) = call.stream().next().await {
-        println!("{:#?}", msg?);
+        print_message(&msg?, json)?;
     }
 
     Ok(())
 }
 
-async fn run_listener_settings(mut service: MaestroService) -> anyhow::Result<()> {
+async fn run_listener_settings(mut service: MaestroService, json: bool) -> anyhow::Result<()> {
     let mut call = service.subscribe_to_settings_changes()?;
     while let Some(msg) = call.stream().next().await {
-        println!("{:#?}", msg?);
+        print_message(&msg?, json)?;
+    }
+
+    Ok(())
+}
+
+/// Print a message, either as single line of JSON or in human-readable form.
+#[cfg(feature = "serde")]
+fn print_message<M: Display + serde::Serialize>(msg: &M, json: bool) -> anyhow::Result<()> {
+    if json {
+        println!("{}", maestro::protocol::types::to_json(msg)?);
+    } else {
+        println!("{}", msg);
     }
+    Ok(())
+}
 
+/// Print a message in human-readable form.
+#[cfg(not(feature = "serde"))]
+fn print_message<M: Display>(msg: &M, _json: bool) -> anyhow::Result<()> {
+    println!("{}", msg);
     Ok(())
 }
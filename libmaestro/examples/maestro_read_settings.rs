@@ -10,8 +10,8 @@ use std::str::FromStr;
 use anyhow::bail;
 use bluer::{Address, Session};
 
+use maestro::connection::{self, RetryOptions};
 use maestro::protocol::codec::Codec;
-use maestro::protocol::utils;
 use maestro::pwrpc::client::{Client, ClientHandle};
 use maestro::service::MaestroService;
 use maestro::service::settings::{self, SettingId};
@@ -54,7 +54,7 @@ async fn main() -> Result<(), anyhow::Error> {
     println!();
 
     println!("Connecting to Maestro profile");
-    let stream = common::connect_maestro_rfcomm(&session, &dev).await?;
+    let stream = connection::connect_with_retry(&session, &dev, &RetryOptions::default()).await?;
 
     println!("Profile connected");
 
@@ -66,8 +66,8 @@ async fn main() -> Result<(), anyhow::Error> {
     let mut client = Client::new(stream);
     let handle = client.handle();
 
-    // retreive the channel numer
-    let channel = utils::resolve_channel(&mut client).await?;
+    // discover the channel number
+    let channel = common::discover_channel(&mut client).await?;
 
     let exec_task = common::run_client(client);
     let settings_task = read_settings(handle, channel);
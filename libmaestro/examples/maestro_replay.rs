@@ -0,0 +1,265 @@
+//! Example for replaying a capture recorded via `maestro_dump`.
+//!
+//! This re-issues all calls found in the capture via the RPC client, using
+//! the capture itself as transport, and prints a summary of which calls
+//! succeeded or failed. Request payloads are re-sent as recorded, calls
+//! cancelled in the capture are cancelled again. No device is required.
+//!
+//! Usage:
+//!   cargo run --features serde --example maestro_replay -- --input <file>
+
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use bytes::{Buf, BufMut, Bytes};
+use futures::StreamExt;
+
+use maestro::pwrpc::capture::{self, Direction, Record};
+use maestro::pwrpc::client::{CallUid, Client, ClientHandle, Request, StreamResponse, UnaryResponse};
+use maestro::pwrpc::types::{PacketType, RpcPacket};
+use maestro::pwrpc::Error;
+use maestro::service::methods;
+
+
+const USAGE: &str = "usage: maestro_replay --input <file>";
+
+/// Time to wait for further packets of a call before considering it idle.
+const IDLE_TIMEOUT: Duration = Duration::from_millis(500);
+
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), anyhow::Error> {
+    tracing_subscriber::fmt::init();
+
+    // handle command line arguments
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let input = match args.as_slice() {
+        [flag, input] if flag == "--input" => input,
+        _ => bail!("invalid arguments\n{USAGE}"),
+    };
+
+    let data = std::fs::read(input)
+        .with_context(|| format!("failed to read input file '{input}'"))?;
+
+    // we need the records to know which calls to issue...
+    let records = serde_json::Deserializer::from_slice(&data)
+        .into_iter::<Record>()
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("failed to parse capture '{input}'"))?;
+
+    // ...and the capture as transport to replay them against
+    let replay = capture::replay_from_reader(&data[..])?;
+
+    let mut client = Client::new(replay);
+    let handle = client.handle();
+
+    let summary = tokio::select! {
+        res = client.run() => {
+            res?;
+            bail!("client terminated unexpectedly");
+        },
+        res = replay_calls(handle, &records) => res?,
+    };
+
+    print_summary(&summary);
+    Ok(())
+}
+
+
+/// Message forwarding its encoded representation as-is, used for re-sending
+/// recorded request payloads without knowing their type.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct RawMessage(Bytes);
+
+impl prost::Message for RawMessage {
+    fn encode_raw<B: BufMut>(&self, buf: &mut B) {
+        buf.put_slice(&self.0);
+    }
+
+    fn merge_field<B: Buf>(
+        &mut self,
+        tag: u32,
+        wire_type: prost::encoding::WireType,
+        buf: &mut B,
+        ctx: prost::encoding::DecodeContext,
+    ) -> Result<(), prost::DecodeError> {
+        // only used for sending, responses are decoded as `()`
+        prost::encoding::skip_field(wire_type, tag, buf, ctx)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+
+enum Response {
+    Unary(UnaryResponse<()>),
+    Stream(StreamResponse<()>),
+}
+
+struct PendingCall {
+    uid: CallUid,
+    response: Response,
+    cancelled: bool,
+}
+
+enum Outcome {
+    Ok { items: Option<usize>, open: bool },
+    Cancelled,
+    Failed(Error),
+}
+
+struct CallSummary {
+    uid: CallUid,
+    outcome: Outcome,
+}
+
+async fn replay_calls(mut handle: ClientHandle, records: &[Record]) -> anyhow::Result<Vec<CallSummary>> {
+    let mut pending: Vec<PendingCall> = Vec::new();
+    let mut summary = Vec::new();
+
+    let sent = records.iter()
+        .filter(|r| r.direction == Direction::Tx)
+        .map(|r| &r.packet);
+
+    for packet in sent {
+        let uid = packet_uid(packet);
+
+        match PacketType::from_i32(packet.r#type) {
+            Some(PacketType::Request) => {
+                // a call with the same ID must complete before re-issuing it
+                if let Some(index) = pending.iter().position(|c| c.uid == uid) {
+                    summary.push(finish(pending.remove(index)).await);
+                }
+
+                let req = Request {
+                    channel_id: packet.channel_id,
+                    service_id: packet.service_id.into(),
+                    method_id: packet.method_id.into(),
+                    call_id: packet.call_id.into(),
+                    message: RawMessage(packet.payload.clone()),
+                    deadline: None,
+                };
+
+                let response = if is_stream(records, uid) {
+                    Response::Stream(handle.call_server_stream(req)?)
+                } else {
+                    Response::Unary(handle.call_unary(req)?)
+                };
+
+                pending.push(PendingCall { uid, response, cancelled: false });
+            },
+            Some(PacketType::ClientError) => {
+                handle.cancel(uid)?;
+
+                if let Some(call) = pending.iter_mut().find(|c| c.uid == uid) {
+                    call.cancelled = true;
+                }
+            },
+            _ => {
+                tracing::warn!("skipping sent packet of unsupported type: {:?}", packet);
+            },
+        }
+    }
+
+    for call in pending {
+        summary.push(finish(call).await);
+    }
+
+    Ok(summary)
+}
+
+/// Whether the capture contains stream items for the given call.
+fn is_stream(records: &[Record], uid: CallUid) -> bool {
+    records.iter()
+        .filter(|r| r.direction == Direction::Rx)
+        .filter(|r| r.packet.r#type == PacketType::ServerStream as i32)
+        .any(|r| packet_uid(&r.packet) == uid)
+}
+
+fn packet_uid(packet: &RpcPacket) -> CallUid {
+    CallUid::new(packet.channel_id, packet.service_id, packet.method_id, packet.call_id)
+}
+
+async fn finish(call: PendingCall) -> CallSummary {
+    let outcome = match call.response {
+        Response::Unary(mut rsp) => {
+            match tokio::time::timeout(IDLE_TIMEOUT, rsp.result()).await {
+                Ok(Ok(())) => Outcome::Ok { items: None, open: false },
+                Ok(Err(_)) if call.cancelled => Outcome::Cancelled,
+                Ok(Err(e)) => Outcome::Failed(e),
+                Err(_) => Outcome::Failed(Error::deadline_exceeded("no response in capture")),
+            }
+        },
+        Response::Stream(mut rsp) => {
+            let mut items = 0;
+
+            let result = loop {
+                match tokio::time::timeout(IDLE_TIMEOUT, rsp.stream().next()).await {
+                    Ok(Some(Ok(()))) => items += 1,
+                    Ok(Some(Err(e))) => break Some(Err(e)),
+                    Ok(None) => break Some(Ok(())),
+                    Err(_) => break None,
+                }
+            };
+
+            match result {
+                Some(Ok(())) if call.cancelled => Outcome::Cancelled,
+                Some(Ok(())) => Outcome::Ok { items: Some(items), open: false },
+                Some(Err(e)) => Outcome::Failed(e),
+                None => {
+                    // the stream was still active when the capture ended;
+                    // cancelling it is not part of the capture
+                    rsp.cancel_on_drop(false);
+                    Outcome::Ok { items: Some(items), open: true }
+                },
+            }
+        },
+    };
+
+    CallSummary { uid: call.uid, outcome }
+}
+
+fn print_summary(summary: &[CallSummary]) {
+    let mut succeeded = 0;
+    let mut failed = 0;
+    let mut cancelled = 0;
+
+    let registry = methods::registry();
+
+    println!("channel  call  method                                           outcome");
+
+    for call in summary {
+        let outcome = match &call.outcome {
+            Outcome::Ok { items, open } => {
+                succeeded += 1;
+
+                match (items, open) {
+                    (None, _) => "ok".to_string(),
+                    (Some(n), false) => format!("ok, {n} items"),
+                    (Some(n), true) => format!("ok, {n} items, still open"),
+                }
+            },
+            Outcome::Cancelled => {
+                cancelled += 1;
+                "cancelled".to_string()
+            },
+            Outcome::Failed(e) => {
+                failed += 1;
+                format!("failed: {e}")
+            },
+        };
+
+        let method = registry.describe(call.uid.service_id(), call.uid.method_id());
+
+        println!("{:<7}  {:<4}  {:<47}  {}", call.uid.channel_id(), call.uid.call_id(), method, outcome);
+    }
+
+    println!();
+    println!("{} calls: {succeeded} succeeded, {failed} failed, {cancelled} cancelled", summary.len());
+}
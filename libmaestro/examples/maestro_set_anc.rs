@@ -0,0 +1,92 @@
+//! Example for setting the active noise cancelling (ANC) mode via the Maestro
+//! service.
+//!
+//! Usage:
+//!   cargo run --example maestro_set_anc -- <bluetooth-device-address> <off|anc|transparency>
+
+mod common;
+
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use bluer::{Address, Session};
+
+use maestro::connection::{self, RetryOptions};
+use maestro::protocol::codec::Codec;
+use maestro::pwrpc::client::{Client, ClientHandle};
+use maestro::service::MaestroService;
+use maestro::service::settings::{self, AncState};
+
+
+const USAGE: &str = "usage: maestro_set_anc <bluetooth-device-address> <off|anc|transparency>";
+
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), anyhow::Error> {
+    tracing_subscriber::fmt::init();
+
+    // handle command line arguments
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [addr, mode] = args.as_slice() else {
+        bail!("invalid number of arguments\n{USAGE}");
+    };
+
+    let addr = Address::from_str(addr)
+        .with_context(|| format!("invalid device address '{addr}'\n{USAGE}"))?;
+
+    let anc_state = match mode.as_str() {
+        "off" => AncState::Off,
+        "anc" => AncState::Active,
+        "transparency" => AncState::Aware,
+        _ => bail!("invalid ANC mode '{mode}'\n{USAGE}"),
+    };
+
+    // set up session
+    let session = Session::new().await?;
+    let adapter = session.default_adapter().await?;
+    let dev = adapter.device(addr)?;
+
+    println!("Connecting to Maestro profile of {}", dev.address());
+    let stream = connection::connect_with_retry(&session, &dev, &RetryOptions::default()).await
+        .context("failed to connect to device")?;
+
+    // set up stream for RPC communication
+    let codec = Codec::new();
+    let stream = codec.wrap(stream);
+
+    // set up RPC client
+    let mut client = Client::new(stream);
+    let handle = client.handle();
+
+    // discover the channel number
+    let channel = common::discover_channel(&mut client).await?;
+
+    let exec_task = common::run_client(client);
+    let anc_task = set_anc(handle, channel, anc_state);
+
+    tokio::select! {
+        res = exec_task => {
+            match res {
+                Ok(_) => bail!("client terminated unexpectedly without error"),
+                Err(e) => Err(e),
+            }
+        },
+        res = anc_task => res,
+    }
+}
+
+async fn set_anc(handle: ClientHandle, channel: u32, anc_state: AncState) -> anyhow::Result<()> {
+    let mut service = MaestroService::new(handle, channel);
+
+    println!("Setting ANC state to '{}'", anc_state);
+
+    service.set_anc_state(anc_state).await
+        .context("failed to set ANC state")?;
+
+    // read back the state to check that the device has applied it
+    let state = service.read_setting(settings::id::CurrentAncrState).await
+        .context("failed to read ANC state")?;
+
+    println!("ANC state is now '{}'", state);
+    Ok(())
+}
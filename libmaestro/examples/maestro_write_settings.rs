@@ -11,9 +11,9 @@ use std::str::FromStr;
 
 use anyhow::bail;
 use bluer::{Address, Session};
-use maestro::protocol::utils;
 use num_enum::FromPrimitive;
 
+use maestro::connection::{self, RetryOptions};
 use maestro::protocol::codec::Codec;
 use maestro::pwrpc::client::{Client, ClientHandle};
 use maestro::service::MaestroService;
@@ -65,7 +65,7 @@ async fn main() -> Result<(), anyhow::Error> {
     println!();
 
     println!("Connecting to Maestro profile");
-    let stream = common::connect_maestro_rfcomm(&session, &dev).await?;
+    let stream = connection::connect_with_retry(&session, &dev, &RetryOptions::default()).await?;
 
     println!("Profile connected");
 
@@ -77,8 +77,8 @@ async fn main() -> Result<(), anyhow::Error> {
     let mut client = Client::new(stream);
     let handle = client.handle();
 
-    // retreive the channel numer
-    let channel = utils::resolve_channel(&mut client).await?;
+    // discover the channel number
+    let channel = common::discover_channel(&mut client).await?;
 
     let exec_task = common::run_client(client);
     let settings_task = read_settings(handle, channel, anc_state);
@@ -0,0 +1,616 @@
+//! Utilities for establishing an RFCOMM connection to the Maestro profile via
+//! BlueZ.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use bluer::{Adapter, Address, Device, DeviceProperty, Session};
+use bluer::rfcomm::{Profile, ProfileHandle, ReqError, Role, Stream};
+
+use futures::StreamExt;
+use futures::stream::{BoxStream, SelectAll};
+
+use tokio::sync::broadcast;
+
+use tracing::Instrument;
+
+use crate::logging::DeviceContext;
+
+use crate::protocol::codec::Codec;
+use crate::protocol::utils;
+use crate::pwrpc::client::Client;
+use crate::pwrpc::{Error, Status};
+use crate::service::MaestroService;
+
+
+/// Options controlling how connection attempts are retried.
+///
+/// The delay between two attempts starts at `initial_delay` and is multiplied
+/// by `multiplier` after each failed attempt, up to `max_delay`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryOptions {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    pub max_attempts: u32,
+}
+
+impl RetryOptions {
+    fn next_delay(&self, delay: Duration) -> Duration {
+        Duration::try_from_secs_f64(delay.as_secs_f64() * self.multiplier)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+}
+
+impl Default for RetryOptions {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(8),
+            multiplier: 2.0,
+            max_attempts: 4,
+        }
+    }
+}
+
+
+/// Environment variable used as fallback for [`ConnectOptions::rfcomm_channel`].
+pub const RFCOMM_CHANNEL_ENV: &str = "MAESTRO_RFCOMM_CHANNEL";
+
+/// Options controlling how the Maestro profile is connected.
+///
+/// By default, the RFCOMM channel is negotiated by BlueZ via SDP. Setting
+/// `rfcomm_channel` (or, if unset, the `MAESTRO_RFCOMM_CHANNEL` environment
+/// variable) passes a fixed channel number to BlueZ when registering the
+/// profile instead. The profile is always registered with `auto_connect`
+/// disabled, so the channel only applies to connections initiated via
+/// [`connect`] and BlueZ will not open the channel on its own when the device
+/// connects.
+///
+/// By default, only paired devices are connected to. With `require_paired`
+/// disabled, BlueZ may connect to any nearby device advertising the Maestro
+/// profile. With `require_connected` enabled, the device must already be
+/// connected, otherwise BlueZ connects it as part of connecting the profile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConnectOptions {
+    pub rfcomm_channel: Option<u8>,
+    pub require_paired: bool,
+    pub require_connected: bool,
+    pub retry: RetryOptions,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            rfcomm_channel: None,
+            require_paired: true,
+            require_connected: false,
+            retry: RetryOptions::default(),
+        }
+    }
+}
+
+impl ConnectOptions {
+    /// The RFCOMM channel to use, falling back to the `MAESTRO_RFCOMM_CHANNEL`
+    /// environment variable if no channel has been set explicitly.
+    pub fn channel(&self) -> Option<u8> {
+        resolve_channel(self.rfcomm_channel, std::env::var(RFCOMM_CHANNEL_ENV).ok())
+    }
+
+    fn check_device(&self, paired: bool, connected: bool) -> Result<(), Error> {
+        if self.require_paired && !paired {
+            return Err(Error::failed_precondition("device is not paired"));
+        }
+
+        if self.require_connected && !connected {
+            return Err(Error::failed_precondition("device is not connected"));
+        }
+
+        Ok(())
+    }
+}
+
+fn resolve_channel(channel: Option<u8>, env: Option<String>) -> Option<u8> {
+    if channel.is_some() {
+        return channel;
+    }
+
+    let value = env?;
+    match value.trim().parse() {
+        Ok(channel @ 1..=30) => Some(channel),
+        _ => {
+            tracing::warn!(value=%value, "ignoring invalid {} value", RFCOMM_CHANNEL_ENV);
+            None
+        },
+    }
+}
+
+
+/// Connect to the Maestro RFCOMM profile of the given device.
+///
+/// Equivalent to [`connect`] with the given retry options and otherwise
+/// default [`ConnectOptions`].
+pub async fn connect_with_retry(session: &Session, dev: &Device, options: &RetryOptions)
+    -> Result<Stream, Error>
+{
+    let options = ConnectOptions { retry: *options, ..Default::default() };
+    connect(session, dev, &options).await
+}
+
+/// Connect to the Maestro RFCOMM profile of the given device.
+///
+/// The profile is (re-)registered with BlueZ on every attempt. Fails with
+/// [`Status::FailedPrecondition`] if the device is not paired or connected as
+/// required by `options`, and with [`Status::Unavailable`] once `max_attempts`
+/// attempts have failed.
+pub async fn connect(session: &Session, dev: &Device, options: &ConnectOptions)
+    -> Result<Stream, Error>
+{
+    if let Err(err) = options.check_device(dev.is_paired().await?, dev.is_connected().await?) {
+        tracing::warn!(address=%dev.address(), "refusing to connect: {}", err.message());
+        return Err(err);
+    }
+
+    let channel = options.channel();
+    let options = &options.retry;
+
+    let mut delay = options.initial_delay;
+    let mut attempt = 1;
+
+    loop {
+        let err = match try_connect(session, dev, channel).await {
+            Ok(stream) => return Ok(stream),
+            Err(err) => err,
+        };
+
+        if attempt >= options.max_attempts {
+            tracing::warn!(error=?err, "connecting to profile failed, giving up ({}/{})",
+                           attempt, options.max_attempts);
+
+            let msg = format!("failed to connect to maestro profile after {attempt} attempts");
+            return Err(Error::extend(Status::Unavailable, msg, err));
+        }
+
+        tracing::warn!(error=?err, "connecting to profile failed, trying again in {:?} ({}/{})",
+                       delay, attempt, options.max_attempts);
+
+        tokio::time::sleep(delay).await;
+
+        delay = options.next_delay(delay);
+        attempt += 1;
+    }
+}
+
+async fn try_connect(session: &Session, dev: &Device, channel: Option<u8>)
+    -> bluer::Result<Stream>
+{
+    let maestro_profile = Profile {
+        uuid: crate::UUID,
+        role: Some(Role::Client),
+        channel: channel.map(u16::from),
+        require_authentication: Some(false),
+        require_authorization: Some(false),
+        auto_connect: Some(false),
+        ..Default::default()
+    };
+
+    tracing::debug!(channel=?channel, "registering maestro profile");
+    let mut handle = session.register_profile(maestro_profile).await?;
+
+    tracing::debug!("connecting to maestro profile");
+    let stream = futures::try_join!(
+        connect_profile(dev),
+        handle_requests_for_profile(&mut handle, dev.address()),
+    )?.1;
+
+    Ok(stream)
+}
+
+async fn connect_profile(dev: &Device) -> bluer::Result<()> {
+    dev.connect_profile(&crate::UUID).await?;
+
+    tracing::debug!(address=%dev.address(), "maestro profile connected");
+    Ok(())
+}
+
+async fn handle_requests_for_profile(handle: &mut ProfileHandle, address: Address)
+    -> bluer::Result<Stream>
+{
+    while let Some(req) = handle.next().await {
+        tracing::debug!(address=%req.device(), "received new profile connection request");
+
+        if req.device() == address {
+            tracing::debug!(address=%req.device(), "accepting profile connection request");
+            return req.accept();
+        } else {
+            req.reject(ReqError::Rejected);
+        }
+    }
+
+    Err(bluer::Error {
+        kind: bluer::ErrorKind::Failed,
+        message: "profile terminated without requests".into(),
+    })
+}
+
+
+/// Connection state change of a watched device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceEvent {
+    Connected(Address),
+    Disconnected(Address),
+}
+
+
+/// Stream of connection state changes for a set of devices.
+///
+/// Events are derived from BlueZ property changes and refer to the Bluetooth
+/// connection of the device, not to the Maestro profile. Use
+/// [`connect_with_retry`] to connect to the Maestro profile once a device has
+/// been connected. As this registers the profile on each call, there is no
+/// registration that needs to be kept alive in between.
+///
+/// The stream stays pending while no device is being watched. It ends once
+/// the event streams of all watched devices have ended, e.g. because BlueZ
+/// has stopped.
+#[derive(Default)]
+pub struct DeviceWatcher {
+    events: SelectAll<BoxStream<'static, DeviceEvent>>,
+    waker: Option<Waker>,
+}
+
+impl DeviceWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start watching the given device.
+    pub async fn watch(&mut self, dev: &Device) -> bluer::Result<()> {
+        let address = dev.address();
+
+        let events = dev.events().await?.filter_map(move |event| async move {
+            device_event(address, &event)
+        });
+
+        tracing::debug!(address=%address, "watching device for connection changes");
+        self.events.push(events.boxed());
+
+        // wake up the task polling us in case we have not watched any
+        // devices before
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+}
+
+impl futures::Stream for DeviceWatcher {
+    type Item = DeviceEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.events.is_empty() {
+            self.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        self.events.poll_next_unpin(cx)
+    }
+}
+
+fn device_event(address: Address, event: &bluer::DeviceEvent) -> Option<DeviceEvent> {
+    match event {
+        bluer::DeviceEvent::PropertyChanged(DeviceProperty::Connected(true)) => {
+            Some(DeviceEvent::Connected(address))
+        },
+        bluer::DeviceEvent::PropertyChanged(DeviceProperty::Connected(false)) => {
+            Some(DeviceEvent::Disconnected(address))
+        },
+        _ => None,
+    }
+}
+
+
+/// Connection state change of a device managed by a [`ConnectionPool`].
+///
+/// Unlike [`DeviceEvent`], these refer to the Maestro profile, i.e., a device
+/// is reported as connected once its service is available via
+/// [`ConnectionPool::get`].
+///
+/// A device is reported as stopped, together with the status code of the
+/// error, if connecting to it failed with an error that is not resolved by
+/// retrying (see [`is_permanent_error`]). The pool does not attempt to
+/// connect to such a device again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolEvent {
+    Connected(Address),
+    Disconnected(Address),
+    Stopped(Address, Status),
+}
+
+/// Whether connecting again after the given error is pointless.
+///
+/// This applies to errors with status `FailedPrecondition` (e.g. the device
+/// not being paired as required by [`ConnectOptions`]), `PermissionDenied`,
+/// and `Unauthenticated`, which require user intervention to be resolved.
+pub fn is_permanent_error(err: &Error) -> bool {
+    matches!(
+        err.code(),
+        Status::FailedPrecondition | Status::PermissionDenied | Status::Unauthenticated
+    )
+}
+
+/// Timeout for probing each channel during channel discovery.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Capacity of the event channel, events are dropped for lagging consumers.
+const EVENT_CAPACITY: usize = 16;
+
+
+/// Connections to the Maestro profile of multiple devices.
+///
+/// The pool connects to all given devices once [`run`](Self::run) is being
+/// polled and maintains one [`MaestroService`] per connected device. Devices
+/// dropping their connection are reconnected once BlueZ reports them as
+/// connected again, using the retry logic of [`connect`] for each attempt. If
+/// only the Maestro profile has been disconnected while the device remains
+/// connected, reconnecting is attempted again after a delay, increasing as
+/// configured by [`RetryOptions`] until a connection has been established.
+/// Devices failing with a permanent error are reported via
+/// [`PoolEvent::Stopped`] and not connected to again.
+///
+/// All methods take `&self`, so services can be accessed while the pool is
+/// running, e.g. via `tokio::select!`.
+pub struct ConnectionPool {
+    session: Session,
+    adapter: Adapter,
+    addresses: Vec<Address>,
+    options: ConnectOptions,
+    services: Mutex<HashMap<Address, MaestroService>>,
+    events: broadcast::Sender<PoolEvent>,
+}
+
+impl ConnectionPool {
+    pub fn new(session: Session, adapter: Adapter, addresses: impl IntoIterator<Item = Address>) -> Self {
+        Self::with_options(session, adapter, addresses, ConnectOptions::default())
+    }
+
+    pub fn with_options(
+        session: Session,
+        adapter: Adapter,
+        addresses: impl IntoIterator<Item = Address>,
+        options: ConnectOptions,
+    ) -> Self {
+        let mut addresses: Vec<_> = addresses.into_iter().collect();
+        addresses.sort();
+        addresses.dedup();
+
+        Self {
+            session,
+            adapter,
+            addresses,
+            options,
+            services: Mutex::new(HashMap::new()),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    /// The addresses of all devices managed by this pool.
+    pub fn addresses(&self) -> &[Address] {
+        &self.addresses
+    }
+
+    /// Return the service of the given device, if it is currently connected.
+    pub fn get(&self, address: Address) -> Option<MaestroService> {
+        self.services.lock().unwrap().get(&address).cloned()
+    }
+
+    /// Return a stream of connection state changes.
+    ///
+    /// The stream only yields events occurring after this call. Use
+    /// [`get`](Self::get) to check for devices connected before.
+    pub fn events(&self) -> BoxStream<'static, PoolEvent> {
+        let rx = self.events.subscribe();
+
+        futures::stream::unfold(rx, |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((event, rx)),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("pool event consumer lagging behind, dropped {} events", n);
+                    },
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        }).boxed()
+    }
+
+    /// Run the pool, connecting and reconnecting all devices.
+    ///
+    /// This only returns once BlueZ stops reporting changes for all devices,
+    /// or with an error if a device could not be watched.
+    pub async fn run(&self) -> Result<(), Error> {
+        let devices = self.addresses.iter().map(|&address| self.run_device(address));
+
+        futures::future::try_join_all(devices).await?;
+        Ok(())
+    }
+
+    async fn run_device(&self, address: Address) -> Result<(), Error> {
+        let dev = self.adapter.device(address)?;
+
+        let mut watcher = DeviceWatcher::new();
+        watcher.watch(&dev).await?;
+
+        let mut delay = self.options.retry.initial_delay;
+
+        loop {
+            if let Err(err) = self.serve(&dev, &mut delay).await {
+                if is_permanent_error(&err) {
+                    tracing::error!(address=%address, error=?err, "maestro connection failed, giving up");
+                    let _ = self.events.send(PoolEvent::Stopped(address, err.code()));
+                    return Ok(());
+                }
+
+                tracing::warn!(address=%address, error=?err, "maestro connection failed");
+            }
+
+            if dev.is_connected().await? {
+                // only the profile has been disconnected, try again later
+                tracing::debug!(address=%address, "reconnecting profile in {:?}", delay);
+
+                tokio::time::sleep(delay).await;
+                delay = self.options.retry.next_delay(delay);
+                continue;
+            }
+
+            tracing::debug!(address=%address, "waiting for device to reconnect");
+            delay = self.options.retry.initial_delay;
+
+            loop {
+                match watcher.next().await {
+                    Some(DeviceEvent::Connected(_)) => break,
+                    Some(DeviceEvent::Disconnected(_)) => continue,
+                    None => return Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Connect to the given device and run its client until the connection
+    /// is lost. Resets the reconnect `delay` once connected.
+    async fn serve(&self, dev: &Device, delay: &mut Duration) -> Result<(), Error> {
+        let address = dev.address();
+
+        let stream = connect(&self.session, dev, &self.options).await?;
+        let stream = Codec::new().wrap(stream);
+
+        let mut client = Client::new(stream);
+        let handle = client.handle();
+
+        let channel = tokio::select! {
+            res = client.run() => {
+                res?;
+                return Err(Error::aborted("client terminated unexpectedly"));
+            },
+            res = utils::discover_channel(&handle, DISCOVERY_TIMEOUT) => res?,
+        };
+
+        self.services.lock().unwrap().insert(address, MaestroService::new(handle, channel));
+        *delay = self.options.retry.initial_delay;
+
+        tracing::debug!(address=%address, channel=channel, "maestro connection established");
+        let _ = self.events.send(PoolEvent::Connected(address));
+
+        let span = DeviceContext::new(address, channel).span();
+        let result = client.run().instrument(span.clone()).await;
+
+        self.services.lock().unwrap().remove(&address);
+        let _ = self.events.send(PoolEvent::Disconnected(address));
+
+        // complete any calls still pending on clones of the removed service
+        client.terminate().instrument(span).await?;
+        result
+    }
+}
+
+impl std::fmt::Debug for ConnectionPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let connected: Vec<_> = self.services.lock().unwrap().keys().copied().collect();
+
+        f.debug_struct("ConnectionPool")
+            .field("addresses", &self.addresses)
+            .field("connected", &connected)
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_next_delay() {
+        let opts = RetryOptions {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(3),
+            multiplier: 2.0,
+            max_attempts: 5,
+        };
+
+        let d1 = opts.next_delay(opts.initial_delay);
+        let d2 = opts.next_delay(d1);
+        let d3 = opts.next_delay(d2);
+
+        assert_eq!(d1, Duration::from_secs(1));
+        assert_eq!(d2, Duration::from_secs(2));
+        assert_eq!(d3, Duration::from_secs(3));
+
+        let opts = RetryOptions { multiplier: f64::INFINITY, ..opts };
+        assert_eq!(opts.next_delay(Duration::from_secs(1)), opts.max_delay);
+    }
+
+    #[test]
+    fn test_resolve_channel() {
+        assert_eq!(resolve_channel(None, None), None);
+        assert_eq!(resolve_channel(Some(3), None), Some(3));
+        assert_eq!(resolve_channel(Some(3), Some("5".into())), Some(3));
+        assert_eq!(resolve_channel(None, Some(" 5\n".into())), Some(5));
+        assert_eq!(resolve_channel(None, Some("0".into())), None);
+        assert_eq!(resolve_channel(None, Some("31".into())), None);
+        assert_eq!(resolve_channel(None, Some("abc".into())), None);
+    }
+
+    #[test]
+    fn test_check_device() {
+        let opts = ConnectOptions::default();
+        assert!(opts.check_device(true, false).is_ok());
+        assert_eq!(opts.check_device(false, true).unwrap_err().code(), Status::FailedPrecondition);
+
+        let opts = ConnectOptions { require_paired: false, require_connected: true, ..opts };
+        assert!(opts.check_device(false, true).is_ok());
+        assert_eq!(opts.check_device(true, false).unwrap_err().code(), Status::FailedPrecondition);
+    }
+
+    #[test]
+    fn test_is_permanent_error() {
+        let opts = ConnectOptions::default();
+        assert!(is_permanent_error(&opts.check_device(false, true).unwrap_err()));
+
+        assert!(is_permanent_error(&Error::permission_denied("not authorized")));
+        assert!(is_permanent_error(&Error::unauthenticated("authentication failed")));
+        assert!(!is_permanent_error(&Error::unavailable("connection refused")));
+        assert!(!is_permanent_error(&Error::deadline_exceeded("no response")));
+    }
+
+    #[test]
+    fn test_device_event() {
+        use bluer::DeviceEvent as Event;
+
+        let addr = Address::new([0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+
+        let event = Event::PropertyChanged(DeviceProperty::Connected(true));
+        assert_eq!(device_event(addr, &event), Some(DeviceEvent::Connected(addr)));
+
+        let event = Event::PropertyChanged(DeviceProperty::Connected(false));
+        assert_eq!(device_event(addr, &event), Some(DeviceEvent::Disconnected(addr)));
+
+        let event = Event::PropertyChanged(DeviceProperty::Paired(true));
+        assert_eq!(device_event(addr, &event), None);
+    }
+
+    #[test]
+    fn test_device_watcher_empty() {
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut watcher = DeviceWatcher::new();
+        assert!(watcher.poll_next_unpin(&mut cx).is_pending());
+    }
+}
@@ -1,3 +1,6 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use super::{decoder, encoder, Frame};
 
 use bytes::BytesMut;
@@ -25,18 +28,105 @@ impl From<decoder::Error> for DecoderError {
 }
 
 
+/// Policy for handling malformed data on the receiving side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecoveryPolicy {
+    /// Return an error, which generally ends the stream.
+    Fail,
+
+    /// Log the error and discard data up to the start of the next frame.
+    #[default]
+    SkipToNextFrame,
+}
+
+
+/// Frame-level counters, updated while decoding and encoding.
+///
+/// Skipped bytes include all bytes of frames that could not be decoded, as
+/// well as any data received outside of frames. Total bytes refer to received
+/// data, encoded bytes to the escaped frames written for sending.
+#[derive(Debug, Default)]
+pub struct CodecStats {
+    frames_decoded: AtomicU64,
+    checksum_errors: AtomicU64,
+    bytes_skipped: AtomicU64,
+    bytes_total: AtomicU64,
+    frames_encoded: AtomicU64,
+    bytes_encoded: AtomicU64,
+}
+
+impl CodecStats {
+    pub fn frames_decoded(&self) -> u64 {
+        self.frames_decoded.load(Ordering::Relaxed)
+    }
+
+    pub fn checksum_errors(&self) -> u64 {
+        self.checksum_errors.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_skipped(&self) -> u64 {
+        self.bytes_skipped.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_total(&self) -> u64 {
+        self.bytes_total.load(Ordering::Relaxed)
+    }
+
+    pub fn frames_encoded(&self) -> u64 {
+        self.frames_encoded.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_encoded(&self) -> u64 {
+        self.bytes_encoded.load(Ordering::Relaxed)
+    }
+}
+
+
 #[derive(Debug, Default)]
 pub struct Codec {
     dec: decoder::Decoder,
+    policy: RecoveryPolicy,
+    stats: Arc<CodecStats>,
+
+    /// Bytes consumed for the frame currently being decoded.
+    pending: u64,
 }
 
 impl Codec {
     pub fn new() -> Self {
-        Self { dec: decoder::Decoder::new() }
+        Self::with_capacity(decoder::Decoder::DEFAULT_MAX_FRAME_SIZE)
     }
 
     pub fn with_capacity(cap: usize) -> Self {
-        Self { dec: decoder::Decoder::with_capacity(cap) }
+        Self {
+            dec: decoder::Decoder::with_capacity(cap),
+            policy: RecoveryPolicy::default(),
+            stats: Arc::new(CodecStats::default()),
+            pending: 0,
+        }
+    }
+
+    /// Set the policy for handling malformed frames. Defaults to
+    /// [`RecoveryPolicy::SkipToNextFrame`].
+    pub fn with_error_recovery(mut self, policy: RecoveryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn max_frame_size(&self) -> usize {
+        self.dec.max_frame_size()
+    }
+
+    pub fn recovery_policy(&self) -> RecoveryPolicy {
+        self.policy
+    }
+
+    /// Return the decoding statistics of this codec.
+    ///
+    /// The statistics are shared and can still be accessed after the codec
+    /// has been moved, e.g. via [`wrap`](Self::wrap).
+    pub fn stats(&self) -> Arc<CodecStats> {
+        self.stats.clone()
     }
 
     pub fn wrap<T>(self, io: T) -> Framed<T, Codec>
@@ -51,7 +141,12 @@ impl tokio_util::codec::Encoder<&Frame> for Codec {
     type Error = std::io::Error;
 
     fn encode(&mut self, frame: &Frame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let len = dst.len();
         encoder::encode(dst, frame);
+
+        self.stats.frames_encoded.fetch_add(1, Ordering::Relaxed);
+        self.stats.bytes_encoded.fetch_add((dst.len() - len) as u64, Ordering::Relaxed);
+
         Ok(())
     }
 }
@@ -61,12 +156,90 @@ impl tokio_util::codec::Decoder for Codec {
     type Error = std::io::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        match self.dec.process(src) {
-            Ok(x) => Ok(x),
-            Err(e) => {
-                tracing::warn!("error decoding data: {e:?}");
-                Ok(None)
-            },
+        // Note: We need to continue decoding after an error as there may be
+        // a complete frame remaining in the buffer. Returning Ok(None) would
+        // stall it until new data has been received.
+        loop {
+            let len = src.len();
+            let result = self.dec.process(src);
+            let consumed = (len - src.len()) as u64;
+
+            self.stats.bytes_total.fetch_add(consumed, Ordering::Relaxed);
+
+            match result {
+                Ok(Some(frame)) => {
+                    self.stats.frames_decoded.fetch_add(1, Ordering::Relaxed);
+                    self.pending = 0;
+
+                    return Ok(Some(frame));
+                },
+                Ok(None) => {
+                    self.pending += consumed;
+                    return Ok(None);
+                },
+                Err(e) => {
+                    self.stats.bytes_skipped.fetch_add(self.pending + consumed, Ordering::Relaxed);
+                    self.pending = 0;
+
+                    if matches!(e, decoder::Error::InvalidChecksum { .. }) {
+                        self.stats.checksum_errors.fetch_add(1, Ordering::Relaxed);
+                    }
+
+                    match self.policy {
+                        RecoveryPolicy::Fail => {
+                            let msg = format!("error decoding data: {e:?}");
+                            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, msg));
+                        },
+                        RecoveryPolicy::SkipToNextFrame => {
+                            tracing::warn!("error decoding data, skipping to next frame: {e:?}");
+                        },
+                    }
+                },
+            }
         }
     }
 }
+
+
+#[cfg(test)]
+mod test {
+    use tokio_util::codec::Decoder;
+
+    use super::*;
+
+    #[test]
+    fn test_recovery() {
+        let frame = Frame {
+            address: 0x010203,
+            control: 0x03,
+            data: vec![0x05, 0x06, 0x07].into(),
+        };
+
+        // garbage, corrupted frame, and valid frame
+        let mut data = BytesMut::from(&[0x01, 0x02][..]);
+        let mut corrupted = frame.encode_bytes();
+        let n = corrupted.len();
+        corrupted[n - 2] ^= 0xff;
+
+        data.extend_from_slice(&corrupted);
+        data.extend_from_slice(&frame.encode_bytes());
+
+        let mut codec = Codec::new();
+        let mut buf = data.clone();
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(frame.clone()));
+        assert!(buf.is_empty());
+
+        let stats = codec.stats();
+        assert_eq!(stats.frames_decoded(), 1);
+        assert_eq!(stats.checksum_errors(), 1);
+        assert_eq!(stats.bytes_total(), data.len() as u64);
+
+        // the end flag of the corrupted frame is shared with the valid frame
+        let valid = frame.encode_bytes().len() as u64 - 1;
+        assert_eq!(stats.bytes_skipped(), data.len() as u64 - valid);
+
+        let mut codec = Codec::new().with_error_recovery(RecoveryPolicy::Fail);
+        let mut buf = data;
+        assert!(codec.decode(&mut buf).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, BufMut, BytesMut};
 
 use super::consts;
 use super::crc;
@@ -10,11 +10,11 @@ use super::Frame;
 pub enum Error {
     UnexpectedData,
     UnexpectedEndOfFrame,
-    InvalidChecksum,
+    InvalidChecksum { expected: u32, actual: u32 },
     InvalidEncoding,
     InvalidFrame,
     InvalidAddress,
-    BufferOverflow,
+    FrameTooLarge { actual: usize, limit: usize },
 }
 
 impl From<varint::DecodeError> for Error {
@@ -29,9 +29,10 @@ impl From<varint::DecodeError> for Error {
 
 #[derive(Debug)]
 pub struct Decoder {
-    buf: Vec<u8>,
+    buf: BytesMut,
     state: (State, EscState),
     current_frame_size: usize,
+    max_frame_size: usize,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -47,18 +48,29 @@ enum EscState {
 }
 
 impl Decoder {
+    pub const DEFAULT_MAX_FRAME_SIZE: usize = 4096;
+
     pub fn new() -> Self {
-        Self::with_capacity(4096)
+        Self::with_capacity(Self::DEFAULT_MAX_FRAME_SIZE)
     }
 
+    /// Create a new decoder with the given maximum (unescaped) frame size.
+    ///
+    /// At most this many bytes of a frame are buffered. Larger frames are
+    /// discarded with [`Error::FrameTooLarge`].
     pub fn with_capacity(cap: usize) -> Self {
         Self {
-            buf: Vec::with_capacity(cap),
+            buf: BytesMut::with_capacity(cap),
             state: (State::Discard, EscState::Normal),
             current_frame_size: 0,
+            max_frame_size: cap,
         }
     }
 
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
     pub fn process(&mut self, buf: &mut BytesMut) -> Result<Option<Frame>, Error> {
         if buf.is_empty() {
             return Ok(None);
@@ -89,6 +101,11 @@ impl Decoder {
                                 buf.len()
                             };
 
+                            // only a potential start remains, wait for more data
+                            if n == 0 {
+                                return Ok(None);
+                            }
+
                             buf.advance(n);
                             return Err(Error::UnexpectedData);
                         },
@@ -136,6 +153,18 @@ impl Decoder {
     }
 
     fn decode_buffered(&mut self) -> Result<Option<Frame>, Error> {
+        // check for overflow
+        if self.current_frame_size > self.buf.len() {
+            let err = Error::FrameTooLarge {
+                actual: self.current_frame_size,
+                limit: self.max_frame_size,
+            };
+
+            self.reset();
+            self.state.0 = State::Frame;        // the next frame may already start
+            return Err(err);
+        }
+
         // validate minimum frame size
         if self.buf.len() < 6 {
             self.reset();
@@ -151,17 +180,17 @@ impl Decoder {
         if crc_expect != crc_actual {
             self.reset();
             self.state.0 = State::Frame;        // the next frame may already start
-            return Err(Error::InvalidChecksum);
-        }
-
-        // check for overflow
-        if self.current_frame_size > self.buf.len() {
-            self.reset();
-            return Err(Error::BufferOverflow);
+            return Err(Error::InvalidChecksum { expected: crc_expect, actual: crc_actual });
         }
 
         // decode address
-        let (address, n) = varint::decode(&self.buf)?;
+        let (address, n) = match varint::decode(&self.buf) {
+            Ok(x) => x,
+            Err(e) => {
+                self.reset();
+                return Err(e.into());
+            },
+        };
 
         // validate minimum remaining frame size
         if self.buf.len() < n + 5 {
@@ -169,9 +198,11 @@ impl Decoder {
             return Err(Error::InvalidFrame);
         }
 
-        // get control byte and data
+        // get control byte and data, without copying the buffered frame
         let control = self.buf[n];
-        let data = self.buf[n+1..self.buf.len()-4].into();
+
+        let buf = self.buf.split().freeze();
+        let data = buf.slice(n+1..buf.len()-4);
 
         let frame = Frame {
             address,
@@ -186,8 +217,8 @@ impl Decoder {
     fn push_byte(&mut self, byte: u8) {
         self.current_frame_size += 1;
 
-        if self.buf.len() < self.buf.capacity() {
-            self.buf.push(byte);
+        if self.buf.len() < self.max_frame_size {
+            self.buf.put_u8(byte);
         }
     }
 
@@ -310,7 +341,7 @@ mod test {
         buf.put_slice(&data[..10]);
         buf.put_slice(&data[..]);
 
-        assert_eq!(dec.process(&mut buf), Err(Error::InvalidChecksum));
+        assert!(matches!(dec.process(&mut buf), Err(Error::InvalidChecksum { .. })));
         assert_eq!(dec.process(&mut buf), Ok(Some(expect.clone())));
         assert_eq!(buf.remaining(), 2);
 
@@ -324,5 +355,38 @@ mod test {
         assert_eq!(dec.process(&mut buf), Ok(Some(expect.clone())));
         assert_eq!(buf.remaining(), 2);
 
+        // test start flag received separately from the rest of the frame
+        let mut buf = BytesMut::from(&data[..1]);
+        assert_eq!(dec.process(&mut buf), Ok(None));
+        assert_eq!(buf.remaining(), 1);
+
+        buf.put_slice(&data[1..data.len()-2]);
+        assert_eq!(dec.process(&mut buf), Ok(Some(expect.clone())));
+        assert_eq!(buf.remaining(), 0);
+    }
+
+    #[test]
+    fn test_frame_too_large() {
+        let data = [
+            0x7e, 0x06, 0x08, 0x09, 0x03, 0x05, 0x06, 0x07, 0x7d, 0x5d,
+            0x7d, 0x5e, 0x7f, 0xff, 0xe6, 0x2d, 0x17, 0xc6, 0x7e,
+        ];
+
+        let expect = Frame {
+            address: 0x010203,
+            control: 0x03,
+            data: vec![0x05, 0x06, 0x07, 0x7D, 0x7E, 0x7F, 0xFF].into(),
+        };
+
+        // frame exactly fits
+        let mut dec = Decoder::with_capacity(15);
+        let mut buf = BytesMut::from(&data[..]);
+        assert_eq!(dec.process(&mut buf), Ok(Some(expect)));
+
+        // frame exceeds limit
+        let mut dec = Decoder::with_capacity(14);
+        let mut buf = BytesMut::from(&data[..]);
+        assert_eq!(dec.process(&mut buf), Err(Error::FrameTooLarge { actual: 15, limit: 14 }));
+        assert_eq!(buf.remaining(), 0);
     }
 }
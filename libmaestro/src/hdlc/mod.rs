@@ -7,16 +7,16 @@ pub mod decoder;
 pub mod encoder;
 pub mod varint;
 
-pub use codec::Codec;
+pub use codec::{Codec, CodecStats, RecoveryPolicy};
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Frame {
     pub address: u32,
     pub control: u8,
-    pub data: Box<[u8]>,
+    pub data: Bytes,
 }
 
 impl Frame {
@@ -1,6 +1,20 @@
 //! Library for the Maestro protocol used to change settings (ANC, equalizer,
 //! etc.) on the Google Pixel Buds Pro. Might support other Pixel Buds, might
 //! not.
+//!
+//! # Compatibility
+//!
+//! Enums describing values reported by the device, such as [`AncState`] or
+//! [`SettingId`], as well as settings and events are marked as
+//! `#[non_exhaustive]`, as new firmware versions may add further variants.
+//! Matches on them therefore need a wildcard arm. Values not known to this
+//! library are mapped to an `Unknown` variant carrying the raw value instead
+//! of causing an error. Once a value becomes known, it is moved to its own
+//! variant, so code relying on specific `Unknown` values should match on the
+//! new variant instead when upgrading.
+//!
+//! [`AncState`]: service::settings::AncState
+//! [`SettingId`]: service::settings::SettingId
 
 use uuid::{uuid, Uuid};
 
@@ -9,6 +23,12 @@ use uuid::{uuid, Uuid};
 /// Defined as `25e97ff7-24ce-4c4c-8951-f764a708f7b5`.
 pub const UUID: Uuid = uuid!("25e97ff7-24ce-4c4c-8951-f764a708f7b5");
 
+#[cfg(feature = "bluer")]
+pub mod connection;
+
+#[cfg(feature = "bluer")]
+pub mod logging;
+
 pub mod hdlc;
 pub mod protocol;
 pub mod pwrpc;
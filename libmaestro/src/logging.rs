@@ -0,0 +1,53 @@
+//! Logging context for distinguishing messages of multiple devices.
+
+use bluer::Address;
+
+
+/// Device and channel a connection refers to.
+///
+/// Log messages emitted inside the [`span`](Self::span) of a context carry
+/// its address and channel as `tracing` fields. With the default format of
+/// `tracing-subscriber`, these messages are prefixed with
+/// `device{address=AA:BB:CC:DD:EE:FF channel=1}:`. The [`Display`]
+/// representation of the context is `[AA:BB:CC:DD:EE:FF ch=1]`, e.g. for
+/// prefixing messages manually.
+///
+/// [`Display`]: std::fmt::Display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceContext {
+    pub address: Address,
+    pub channel: u32,
+}
+
+impl DeviceContext {
+    pub fn new(address: Address, channel: u32) -> Self {
+        Self { address, channel }
+    }
+
+    /// Create a span carrying this context.
+    ///
+    /// Enter the span or attach it to a future via
+    /// [`Instrument`](tracing::Instrument) to add the context to all
+    /// messages logged from within.
+    pub fn span(&self) -> tracing::Span {
+        tracing::info_span!("device", address = %self.address, channel = self.channel)
+    }
+}
+
+impl std::fmt::Display for DeviceContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{} ch={}]", self.address, self.channel)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let ctx = DeviceContext::new(Address::new([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]), 1);
+        assert_eq!(ctx.to_string(), "[AA:BB:CC:DD:EE:FF ch=1]");
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use bytes::BytesMut;
 
 use prost::Message;
@@ -6,20 +8,142 @@ use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_util::codec::{Decoder, Framed, Encoder};
 
 use crate::pwrpc::types::RpcPacket;
-use crate::hdlc;
+use crate::hdlc::{self, CodecStats, RecoveryPolicy};
 
 use super::addr;
 
 
+/// Default limit for the size of received frames.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = hdlc::decoder::Decoder::DEFAULT_MAX_FRAME_SIZE;
+
+/// Maximum number of bytes an (unescaped) frame adds to the packet payload.
+///
+/// This consists of the HDLC address (up to 5 bytes), control (1 byte), and
+/// checksum (4 bytes), as well as the protobuf encoding of all packet fields
+/// apart from the payload data (45 bytes at most).
+pub const MAX_FRAME_OVERHEAD: usize = 10 + 45;
+
+/// Default limit for the payload size of sent packets.
+///
+/// Packets with payloads up to this size fit into frames of
+/// [`DEFAULT_MAX_FRAME_SIZE`], i.e., are accepted by the default decoder.
+pub const DEFAULT_MAX_PAYLOAD_SIZE: usize = DEFAULT_MAX_FRAME_SIZE - MAX_FRAME_OVERHEAD;
+
+
+/// Error for packets exceeding the payload limit of the codec.
+///
+/// Encoding such a packet fails with an [`std::io::Error`] of kind
+/// [`InvalidInput`](std::io::ErrorKind::InvalidInput), wrapping this error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayloadTooLarge {
+    pub size: usize,
+    pub limit: usize,
+}
+
+impl std::fmt::Display for PayloadTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "packet payload too large: {} bytes, limit is {} bytes", self.size, self.limit)
+    }
+}
+
+impl std::error::Error for PayloadTooLarge {}
+
+
 pub struct Codec {
     hdlc: hdlc::Codec,
+    max_payload_size: usize,
 }
 
 impl Codec {
     pub fn new() -> Self {
         Self {
             hdlc: hdlc::Codec::new(),
+            max_payload_size: DEFAULT_MAX_PAYLOAD_SIZE,
+        }
+    }
+
+    /// Create a new codec limiting the size of received frames.
+    ///
+    /// Frames exceeding the limit are dropped without buffering them. The
+    /// limit refers to the unescaped HDLC frame, including address, control,
+    /// and checksum fields. Defaults to [`DEFAULT_MAX_FRAME_SIZE`].
+    ///
+    /// The payload limit for sent packets is set accordingly, i.e., to the
+    /// frame size minus [`MAX_FRAME_OVERHEAD`].
+    pub fn with_max_frame_size(size: usize) -> Self {
+        Self {
+            hdlc: hdlc::Codec::with_capacity(size),
+            max_payload_size: size.saturating_sub(MAX_FRAME_OVERHEAD),
+        }
+    }
+
+    /// Set the policy for handling malformed frames and packets. Defaults to
+    /// [`RecoveryPolicy::SkipToNextFrame`].
+    pub fn with_error_recovery(self, policy: RecoveryPolicy) -> Self {
+        Self {
+            hdlc: self.hdlc.with_error_recovery(policy),
+            ..self
+        }
+    }
+
+    /// Limit the payload size of sent packets.
+    ///
+    /// Encoding a packet with a larger payload fails with an error of kind
+    /// [`InvalidInput`](std::io::ErrorKind::InvalidInput), wrapping a
+    /// [`PayloadTooLarge`] error, and nothing is written to the transport.
+    /// Defaults to [`DEFAULT_MAX_PAYLOAD_SIZE`].
+    pub fn with_max_payload_size(self, size: usize) -> Self {
+        Self {
+            max_payload_size: size,
+            ..self
+        }
+    }
+
+    pub fn max_frame_size(&self) -> usize {
+        self.hdlc.max_frame_size()
+    }
+
+    pub fn max_payload_size(&self) -> usize {
+        self.max_payload_size
+    }
+
+    pub fn recovery_policy(&self) -> RecoveryPolicy {
+        self.hdlc.recovery_policy()
+    }
+
+    /// Return the frame-level decoding statistics of this codec.
+    ///
+    /// The statistics are shared and can still be accessed after the codec
+    /// has been moved, e.g. via [`wrap`](Self::wrap). Frames with unexpected
+    /// control type or undecodable packets are counted as decoded frames.
+    pub fn stats(&self) -> Arc<CodecStats> {
+        self.hdlc.stats()
+    }
+
+    /// Encode a single packet to a new buffer, without any transport.
+    ///
+    /// The limits and statistics of this codec apply as for packets sent
+    /// via [`wrap`](Self::wrap).
+    pub fn encode_to_vec(&mut self, packet: &RpcPacket) -> Result<Vec<u8>, std::io::Error> {
+        let mut buf = BytesMut::new();
+        self.encode(packet, &mut buf)?;
+        Ok(buf.to_vec())
+    }
+
+    /// Decode all complete packets in the given data, without any transport.
+    ///
+    /// Trailing data not forming a complete frame is ignored. Malformed
+    /// frames and packets are handled according to the recovery policy of
+    /// this codec.
+    pub fn decode_from_slice(&mut self, data: &[u8]) -> Result<Vec<RpcPacket>, std::io::Error> {
+        let mut buf = BytesMut::from(data);
+        let mut packets = Vec::new();
+
+        while let Some(packet) = self.decode(&mut buf)? {
+            packets.push(packet);
         }
+
+        Ok(packets)
     }
 
     pub fn wrap<T>(self, io: T) -> Framed<T, Codec>
@@ -28,6 +152,19 @@ impl Codec {
     {
         Framed::with_capacity(io, self, 4096 as _)
     }
+
+    /// Wrap the given IO stream, returning the statistics of the codec
+    /// alongside it.
+    ///
+    /// The statistics are updated as packets are received and sent and can
+    /// be read from any thread without locking.
+    pub fn wrap_with_stats<T>(self, io: T) -> (Framed<T, Codec>, Arc<CodecStats>)
+    where
+        T: AsyncRead + AsyncWrite,
+    {
+        let stats = self.stats();
+        (self.wrap(io), stats)
+    }
 }
 
 impl Default for Codec {
@@ -41,18 +178,24 @@ impl Decoder for Codec {
     type Error = std::io::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        match self.hdlc.decode(src)? {
-            Some(frame) => {
-                if frame.control != 0x03 {
-                    tracing::warn!("unexpected control type: {}", frame.control);
-                    return Ok(None);
-                }
+        while let Some(frame) = self.hdlc.decode(src)? {
+            if frame.control != 0x03 {
+                tracing::warn!("unexpected control type: {}", frame.control);
+                continue;
+            }
 
-                let packet = RpcPacket::decode(&frame.data[..])?;
-                Ok(Some(packet))
+            match RpcPacket::decode(frame.data) {
+                Ok(packet) => return Ok(Some(packet)),
+                Err(e) => match self.recovery_policy() {
+                    RecoveryPolicy::Fail => return Err(e.into()),
+                    RecoveryPolicy::SkipToNextFrame => {
+                        tracing::warn!("error decoding packet, skipping frame: {e:?}");
+                    },
+                },
             }
-            None => Ok(None),
         }
+
+        Ok(None)
     }
 }
 
@@ -60,7 +203,15 @@ impl Encoder<&RpcPacket> for Codec {
     type Error = std::io::Error;
 
     fn encode(&mut self, packet: &RpcPacket, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let address = addr::address_for_channel(packet.channel_id).unwrap();
+        if packet.payload_len() > self.max_payload_size {
+            let err = PayloadTooLarge { size: packet.payload_len(), limit: self.max_payload_size };
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, err));
+        }
+
+        let address = addr::address_for_channel(packet.channel_id).ok_or_else(|| {
+            let msg = format!("no address for channel: {}", packet.channel_id);
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, msg)
+        })?;
 
         let frame = hdlc::Frame {
             address: address.value(),
@@ -79,3 +230,230 @@ impl Encoder<RpcPacket> for Codec {
         self.encode(&packet, dst)
     }
 }
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures::{SinkExt, StreamExt};
+
+    use crate::pwrpc::types::PacketType;
+
+    #[tokio::test]
+    async fn test_wrap_with_stats() {
+        let (a, b) = tokio::io::duplex(1024);
+
+        let (mut a, stats_a) = Codec::new().wrap_with_stats(a);
+        let (mut b, stats_b) = Codec::new().wrap_with_stats(b);
+
+        let packet = RpcPacket::builder()
+            .packet_type(PacketType::Request)
+            .channel_id(18)
+            .service_id(1)
+            .method_id(2)
+            .payload(vec![0x7e, 0x7d, 0x01])
+            .build()
+            .unwrap();
+
+        a.send(packet.clone()).await.unwrap();
+        assert_eq!(b.next().await.unwrap().unwrap(), packet);
+
+        assert_eq!(stats_a.frames_encoded(), 1);
+        assert_eq!(stats_b.frames_decoded(), 1);
+        assert_eq!(stats_a.bytes_encoded(), stats_b.bytes_total());
+        assert_eq!(stats_b.bytes_skipped(), 0);
+
+        assert_eq!(stats_a.frames_decoded(), 0);
+        assert_eq!(stats_b.frames_encoded(), 0);
+    }
+
+    #[test]
+    fn test_max_payload_size() {
+        let mut codec = Codec::new().with_max_payload_size(4);
+        let mut buf = BytesMut::new();
+
+        let packet = |len| {
+            RpcPacket::builder()
+                .packet_type(PacketType::Request)
+                .channel_id(18)
+                .service_id(1)
+                .method_id(2)
+                .payload(vec![0x01; len])
+                .build()
+                .unwrap()
+        };
+
+        codec.encode(packet(4), &mut buf).unwrap();
+        assert!(!buf.is_empty());
+
+        buf.clear();
+
+        let err = codec.encode(packet(5), &mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+
+        let err = err.get_ref().unwrap().downcast_ref::<PayloadTooLarge>();
+        assert_eq!(err, Some(&PayloadTooLarge { size: 5, limit: 4 }));
+    }
+
+    #[test]
+    fn test_default_limits() {
+        // worst case for the encoded size of all fields apart from the payload
+        let packet = RpcPacket {
+            r#type: -1,
+            channel_id: 27,
+            service_id: u32::MAX,
+            method_id: u32::MAX,
+            payload: vec![0x01; DEFAULT_MAX_PAYLOAD_SIZE].into(),
+            status: u32::MAX,
+            call_id: u32::MAX,
+        };
+
+        let data = Codec::new().encode_to_vec(&packet).unwrap();
+        assert_eq!(Codec::new().decode_from_slice(&data).unwrap(), [packet]);
+
+        assert_eq!(Codec::with_max_frame_size(1024).max_payload_size(), 1024 - MAX_FRAME_OVERHEAD);
+    }
+
+    #[test]
+    fn test_encode_to_vec() {
+        let packet = RpcPacket::builder()
+            .packet_type(PacketType::Request)
+            .channel_id(18)
+            .service_id(1)
+            .method_id(2)
+            .payload(vec![0x7e])
+            .build()
+            .unwrap();
+
+        let mut codec = Codec::new();
+        let data = codec.encode_to_vec(&packet).unwrap();
+
+        let mut buf = BytesMut::new();
+        Codec::new().encode(&packet, &mut buf).unwrap();
+        assert_eq!(data, buf);
+
+        assert_eq!(data.first(), Some(&0x7e));
+        assert_eq!(data.last(), Some(&0x7e));
+        assert_eq!(codec.stats().frames_encoded(), 1);
+
+        let decoded = Codec::new().decode(&mut BytesMut::from(&data[..])).unwrap();
+        assert_eq!(decoded, Some(packet));
+
+        let err = Codec::new().with_max_payload_size(0)
+            .encode_to_vec(&RpcPacket { channel_id: 18, payload: vec![0x01].into(), ..Default::default() })
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_decode_from_slice() {
+        let packet = |method_id| {
+            RpcPacket::builder()
+                .packet_type(PacketType::Response)
+                .channel_id(18)
+                .service_id(1)
+                .method_id(method_id)
+                .payload(vec![0x7d, 0x02])
+                .build()
+                .unwrap()
+        };
+
+        let mut codec = Codec::new();
+        let mut data = Vec::new();
+        data.extend(codec.encode_to_vec(&packet(1)).unwrap());
+        data.extend([0x7e, 0x01, 0x02, 0x7e]);                // malformed frame
+        data.extend(codec.encode_to_vec(&packet(2)).unwrap());
+
+        let incomplete = codec.encode_to_vec(&packet(3)).unwrap();
+        data.extend(&incomplete[..incomplete.len() - 1]);
+
+        let packets = Codec::new().decode_from_slice(&data).unwrap();
+        assert_eq!(packets, [packet(1), packet(2)]);
+
+        assert_eq!(Codec::new().decode_from_slice(&[]).unwrap(), []);
+
+        let err = Codec::new().with_error_recovery(RecoveryPolicy::Fail)
+            .decode_from_slice(&data)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_unknown_channel() {
+        let packet = RpcPacket::builder()
+            .packet_type(PacketType::Request)
+            .channel_id(0)
+            .service_id(1)
+            .method_id(2)
+            .build()
+            .unwrap();
+
+        let mut buf = BytesMut::new();
+        let err = Codec::new().encode(packet, &mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+    }
+
+    /// Stable counterpart of the `codec_decode` fuzz target, using
+    /// pseudo-random inputs.
+    #[test]
+    fn test_decode_arbitrary() {
+        let mut state = 0x2545f491u32;
+        let mut next = move || {
+            // xorshift32
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+
+        for _ in 0..2000 {
+            let len = next() as usize % 256;
+
+            // bias towards flag and escape bytes to exercise framing
+            let data: Vec<u8> = (0..len)
+                .map(|_| match next() % 8 {
+                    0 => 0x7e,
+                    1 => 0x7d,
+                    _ => next() as u8,
+                })
+                .collect();
+
+            for policy in [RecoveryPolicy::SkipToNextFrame, RecoveryPolicy::Fail] {
+                let mut codec = Codec::new().with_error_recovery(policy);
+                let mut buf = BytesMut::from(&data[..]);
+
+                while let Ok(Some(packet)) = codec.decode(&mut buf) {
+                    let mut encoded = BytesMut::new();
+                    if Codec::new().encode(&packet, &mut encoded).is_ok() {
+                        let decoded = Codec::new().decode(&mut encoded).unwrap().unwrap();
+                        assert_eq!(decoded, packet);
+                    }
+                }
+            }
+
+            // a valid frame following garbage must still be decoded
+            let packet = RpcPacket::builder()
+                .packet_type(PacketType::ServerStream)
+                .channel_id(18)
+                .service_id(next())
+                .method_id(next())
+                .payload(data.clone())
+                .build()
+                .unwrap();
+
+            let mut buf = BytesMut::from(&data[..]);
+            Codec::new().encode(&packet, &mut buf).unwrap();
+
+            let mut codec = Codec::new();
+            let mut decoded = Vec::new();
+            while let Some(packet) = codec.decode(&mut buf).unwrap() {
+                decoded.push(packet);
+            }
+
+            assert_eq!(decoded.last(), Some(&packet));
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Conversions from raw RPC packets to typed response messages.
+//!
+//! This allows decoding packets that have not been received via the client,
+//! e.g. packets recorded via `PacketCapture` when the `serde` feature is
+//! enabled.
+
+use prost::Message;
+
+use super::types::{
+    HardwareInfo, OobeActionRsp, QuietModeStatusEvent, RuntimeInfo, SettingsRsp, SoftwareInfo,
+};
+use crate::pwrpc::id::IdRef;
+use crate::pwrpc::types::{PacketType, RpcPacket};
+use crate::pwrpc::{Error, Status};
+
+
+/// Method returning a message, used to check that the packet belongs to it.
+struct Method {
+    service: &'static str,
+    method: &'static str,
+    stream: bool,
+}
+
+impl Method {
+    const fn unary(service: &'static str, method: &'static str) -> Self {
+        Method { service, method, stream: false }
+    }
+
+    const fn stream(service: &'static str, method: &'static str) -> Self {
+        Method { service, method, stream: true }
+    }
+
+    fn matches(&self, packet: &RpcPacket) -> bool {
+        packet.service_id == IdRef::new(self.service).hash()
+            && packet.method_id == IdRef::new(self.method).hash()
+    }
+}
+
+fn decode<M>(packet: RpcPacket, methods: &[Method]) -> Result<M, Error>
+where
+    M: Message + Default,
+{
+    let method = methods.iter()
+        .find(|m| m.matches(&packet))
+        .ok_or_else(|| Error::invalid_argument("packet does not belong to a method returning this message"))?;
+
+    match packet.packet_type() {
+        Some(PacketType::Response) if !method.stream => {
+            match Status::from(packet.status) {
+                Status::Ok => Ok(M::decode(packet.payload)?),
+                status => Err(Error::remote(status)),
+            }
+        },
+        Some(PacketType::Response) => {
+            // end of a server stream, does not carry a message
+            match Status::from(packet.status) {
+                Status::Ok => Err(Error::invalid_argument("packet does not carry a message")),
+                status => Err(Error::remote(status)),
+            }
+        },
+        Some(PacketType::ServerStream) if method.stream => {
+            Ok(M::decode(packet.payload)?)
+        },
+        Some(PacketType::ServerError) => {
+            Err(Error::remote(Status::from(packet.status)))
+        },
+        Some(ty) => {
+            Err(Error::invalid_argument(format!("unexpected packet type: {ty}")))
+        },
+        None => {
+            Err(Error::invalid_argument(format!("unknown packet type: {}", packet.r#type)))
+        },
+    }
+}
+
+macro_rules! impl_try_from_packet {
+    ($ty:ty, [$($method:expr),+ $(,)?]) => {
+        impl TryFrom<RpcPacket> for $ty {
+            type Error = Error;
+
+            fn try_from(packet: RpcPacket) -> Result<Self, Self::Error> {
+                decode(packet, &[$($method),+])
+            }
+        }
+    };
+}
+
+impl_try_from_packet!(SoftwareInfo, [
+    Method::unary("maestro_pw.Maestro", "GetSoftwareInfo"),
+]);
+
+impl_try_from_packet!(HardwareInfo, [
+    Method::unary("maestro_pw.Maestro", "GetHardwareInfo"),
+]);
+
+impl_try_from_packet!(RuntimeInfo, [
+    Method::stream("maestro_pw.Maestro", "SubscribeRuntimeInfo"),
+]);
+
+impl_try_from_packet!(SettingsRsp, [
+    Method::unary("maestro_pw.Maestro", "ReadSetting"),
+    Method::stream("maestro_pw.Maestro", "SubscribeToSettingsChanges"),
+]);
+
+impl_try_from_packet!(OobeActionRsp, [
+    Method::stream("maestro_pw.Maestro", "SubscribeToOobeActions"),
+]);
+
+impl_try_from_packet!(QuietModeStatusEvent, [
+    Method::stream("maestro_pw.Multipoint", "SubscribeToQuietModeStatus"),
+]);
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::pwrpc::testing;
+
+    fn request(service: &str, method: &str) -> RpcPacket {
+        RpcPacket::builder()
+            .packet_type(PacketType::Request)
+            .channel_id(18)
+            .service_id(IdRef::new(service).hash())
+            .method_id(IdRef::new(method).hash())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_try_from_packet() {
+        let info = SoftwareInfo { unknown2: 5, ..Default::default() };
+
+        let req = request("maestro_pw.Maestro", "GetSoftwareInfo");
+        let packet = testing::response(&req, &info);
+        assert_eq!(SoftwareInfo::try_from(packet.clone()).unwrap(), info);
+
+        // wrong method
+        let err = HardwareInfo::try_from(packet).unwrap_err();
+        assert_eq!(err.code(), Status::InvalidArgument);
+
+        // error reported by the device
+        let err = SoftwareInfo::try_from(testing::server_error(&req, Status::NotFound)).unwrap_err();
+        assert_eq!(err.code(), Status::NotFound);
+        assert!(err.is_remote());
+
+        // stream items and stream end
+        let req = request("maestro_pw.Maestro", "SubscribeToSettingsChanges");
+        let rsp = SettingsRsp::default();
+
+        let packet = testing::server_stream(&req, &rsp);
+        assert_eq!(SettingsRsp::try_from(packet).unwrap(), rsp);
+
+        let err = SettingsRsp::try_from(testing::response(&req, &())).unwrap_err();
+        assert_eq!(err.code(), Status::InvalidArgument);
+    }
+}
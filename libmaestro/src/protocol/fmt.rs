@@ -0,0 +1,249 @@
+//! Human-readable [`Display`] implementations for the protocol types.
+
+use std::fmt::{Display, Formatter, Result};
+
+use super::types::{
+    BatteryInfo, BatteryState, DeviceBatteryInfo, FirmwareInfo, FirmwareVersion, HardwareInfo,
+    OobeAction, OobeActionRsp, PlacementInfo, RuntimeInfo, SerialNumbers, SettingsRsp,
+    SoftwareInfo,
+};
+
+
+struct OrUnknown<'a, T>(Option<&'a T>);
+
+impl<T: Display> Display for OrUnknown<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self.0 {
+            Some(value) => value.fmt(f),
+            None => write!(f, "unknown"),
+        }
+    }
+}
+
+fn or_unknown<T>(value: Option<&T>) -> OrUnknown<'_, T> {
+    OrUnknown(value)
+}
+
+fn or_unknown_str(value: &str) -> &str {
+    if value.is_empty() { "unknown" } else { value }
+}
+
+
+impl Display for FirmwareVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", or_unknown_str(&self.version_string))
+    }
+}
+
+impl Display for FirmwareInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f, "case {}, left {}, right {}",
+            or_unknown(self.case.as_ref()),
+            or_unknown(self.left.as_ref()),
+            or_unknown(self.right.as_ref()),
+        )
+    }
+}
+
+impl Display for SoftwareInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "firmware: {}", or_unknown(self.firmware.as_ref()))
+    }
+}
+
+impl Display for SerialNumbers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f, "case {}, left {}, right {}",
+            or_unknown_str(&self.case),
+            or_unknown_str(&self.left),
+            or_unknown_str(&self.right),
+        )
+    }
+}
+
+impl Display for HardwareInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "serial numbers: {}", or_unknown(self.serial_number.as_ref()))
+    }
+}
+
+impl Display for BatteryState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            BatteryState::Unknown => write!(f, "unknown"),
+            BatteryState::BatteryNotCharging => write!(f, "not charging"),
+            BatteryState::BatteryCharging => write!(f, "charging"),
+        }
+    }
+}
+
+impl Display for DeviceBatteryInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match BatteryState::from_i32(self.state) {
+            Some(state) => write!(f, "{}% ({})", self.level, state),
+            None => write!(f, "{}% (unknown state: {})", self.level, self.state),
+        }
+    }
+}
+
+impl Display for BatteryInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f, "case {}, left {}, right {}",
+            or_unknown(self.case.as_ref()),
+            or_unknown(self.left.as_ref()),
+            or_unknown(self.right.as_ref()),
+        )
+    }
+}
+
+impl BatteryInfo {
+    /// Concise one-line summary of the battery levels, e.g.
+    /// `L:87% R:92% Case:45%↑`.
+    ///
+    /// Charging components are marked with `↑`, unknown levels are shown as
+    /// `?`.
+    pub fn summary(&self) -> impl Display + '_ {
+        BatterySummary(self)
+    }
+}
+
+struct BatterySummary<'a>(&'a BatteryInfo);
+
+impl Display for BatterySummary<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let component = |f: &mut Formatter<'_>, name, info: Option<&DeviceBatteryInfo>| {
+            let Some(info) = info else {
+                return write!(f, "{name}:?");
+            };
+
+            write!(f, "{name}:{}%", info.level)?;
+
+            if info.state == BatteryState::BatteryCharging as i32 {
+                write!(f, "↑")?;
+            }
+
+            Ok(())
+        };
+
+        component(f, "L", self.0.left.as_ref())?;
+        write!(f, " ")?;
+        component(f, "R", self.0.right.as_ref())?;
+        write!(f, " ")?;
+        component(f, "Case", self.0.case.as_ref())
+    }
+}
+
+impl Display for PlacementInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let placement = |in_case| if in_case { "in case" } else { "out of case" };
+
+        write!(
+            f, "left {}, right {}",
+            placement(self.left_bud_in_case),
+            placement(self.right_bud_in_case),
+        )
+    }
+}
+
+impl Display for RuntimeInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f, "clock: {} ms, battery: {}, placement: {}",
+            self.timestamp_ms,
+            or_unknown(self.battery_info.as_ref()),
+            or_unknown(self.placement.as_ref()),
+        )
+    }
+}
+
+impl Display for SettingsRsp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self.value() {
+            Some(value) => value.fmt(f),
+            None => write!(f, "unknown setting"),
+        }
+    }
+}
+
+impl Display for OobeAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let name = match self {
+            OobeAction::Unknown => "unknown",
+            OobeAction::SingleTap => "single tap",
+            OobeAction::DoubleTap => "double tap",
+            OobeAction::TripleTap => "triple tap",
+            OobeAction::Hold => "hold",
+            OobeAction::SwipeForward => "swipe forward",
+            OobeAction::SwipeBackward => "swipe backward",
+            OobeAction::SwipeUp => "swipe up",
+            OobeAction::SwipeDown => "swipe down",
+            OobeAction::Hotword => "hotword",
+            OobeAction::LeftOnHead => "left on head",
+            OobeAction::LeftOffHead => "left off head",
+            OobeAction::RightOnHead => "right on head",
+            OobeAction::RightOffHead => "right off head",
+            OobeAction::SpeculativeTap => "speculative tap",
+            OobeAction::HoldEnd => "hold end",
+            OobeAction::HoldCancel => "hold cancel",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+impl Display for OobeActionRsp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match OobeAction::from_i32(self.action) {
+            Some(action) => action.fmt(f),
+            None => write!(f, "unknown ({})", self.action),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let info = SoftwareInfo {
+            firmware: Some(FirmwareInfo {
+                case: None,
+                left: Some(FirmwareVersion { version_string: "2.651.0".into(), ..Default::default() }),
+                right: Some(FirmwareVersion::default()),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(info.to_string(), "firmware: case unknown, left 2.651.0, right unknown");
+
+        let info = BatteryInfo {
+            case: None,
+            left: Some(DeviceBatteryInfo { level: 87, state: BatteryState::BatteryCharging as _ }),
+            right: Some(DeviceBatteryInfo { level: 50, state: 7 }),
+        };
+        assert_eq!(info.to_string(), "case unknown, left 87% (charging), right 50% (unknown state: 7)");
+        assert_eq!(info.summary().to_string(), "L:87%↑ R:50% Case:?");
+
+        let info = BatteryInfo {
+            case: Some(DeviceBatteryInfo { level: 45, state: BatteryState::BatteryCharging as _ }),
+            left: Some(DeviceBatteryInfo { level: 87, state: BatteryState::BatteryNotCharging as _ }),
+            right: Some(DeviceBatteryInfo { level: 92, state: BatteryState::Unknown as _ }),
+        };
+        assert_eq!(info.summary().to_string(), "L:87% R:92% Case:45%↑");
+
+        let info = RuntimeInfo {
+            timestamp_ms: 1000,
+            placement: Some(PlacementInfo { right_bud_in_case: true, left_bud_in_case: false }),
+            ..Default::default()
+        };
+        let expected = "clock: 1000 ms, battery: unknown, placement: left out of case, right in case";
+        assert_eq!(info.to_string(), expected);
+
+        assert_eq!(SettingsRsp::default().to_string(), "unknown setting");
+        assert_eq!(OobeActionRsp { action: 4 }.to_string(), "hold");
+    }
+}
@@ -2,6 +2,42 @@ pub mod addr;
 pub mod codec;
 pub mod utils;
 
+mod convert;
+mod fmt;
+mod version;
+
 pub mod types {
     include!(concat!(env!("OUT_DIR"), "/maestro_pw.rs"));
+
+    pub use super::version::SemVer;
+
+    /// Convert the given value, e.g. a protocol message, to JSON.
+    ///
+    /// This allows emitting messages as newline-delimited JSON, for example
+    /// for further processing via tools like `jq`. Serialization does not
+    /// fail for the types of this crate, but may fail for other types, e.g.
+    /// ones containing maps with non-string keys.
+    #[cfg(feature = "serde")]
+    pub fn to_json(value: &impl serde::Serialize) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::to_value(value)
+    }
+}
+
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::types::{self, BatteryInfo, DeviceBatteryInfo};
+
+    #[test]
+    fn test_to_json() {
+        let info = BatteryInfo {
+            left: Some(DeviceBatteryInfo { level: 80, state: 2 }),
+            ..Default::default()
+        };
+
+        let json = types::to_json(&info).unwrap();
+        assert_eq!(json["left"]["level"], 80);
+        assert_eq!(json["left"]["state"], 2);
+        assert!(json["case"].is_null());
+    }
 }
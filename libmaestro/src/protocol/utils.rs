@@ -1,7 +1,12 @@
-use crate::pwrpc::Error;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use futures::FutureExt;
+
+use crate::pwrpc::{Error, Status};
 use crate::pwrpc::client::{Client, Request, UnaryResponse, ClientHandle};
 use crate::pwrpc::id::PathRef;
-use crate::pwrpc::types::RpcPacket;
+use crate::pwrpc::types::{CallId, MethodId, RpcPacket, ServiceId};
 
 use super::addr;
 use super::addr::Peer;
@@ -55,15 +60,16 @@ where
 
 async fn try_open_channel(mut handle: ClientHandle, channel_id: u32) -> Result<u32, Error> {
     let path = PathRef::new("maestro_pw.Maestro/GetSoftwareInfo");
-    let service_id = path.service().hash();
-    let method_id = path.method().hash();
+    let service_id = ServiceId(path.service().hash());
+    let method_id = MethodId(path.method().hash());
 
     let req = Request {
         channel_id,
         service_id,
         method_id,
-        call_id: 0,
+        call_id: CallId(0),
         message: (),
+        deadline: None,
     };
 
     let mut rsp: UnaryResponse<SoftwareInfo> = handle.open_unary(req)?;
@@ -71,3 +77,184 @@ async fn try_open_channel(mut handle: ClientHandle, channel_id: u32) -> Result<u
     rsp.result().await?;
     Ok(channel_id)
 }
+
+
+/// Discover the Maestro channel by actively probing all known channels.
+///
+/// In contrast to [`resolve_channel`], which waits for unsolicited messages
+/// sent by the device, this sends a `GetSoftwareInfo` request on every channel
+/// with a known address mapping and returns the first channel that responds
+/// successfully. Each probe fails with status `DeadlineExceeded` if no
+/// response is received within `timeout`. Once a channel has been found, all
+/// remaining probes are cancelled.
+///
+/// Note that the client needs to be run concurrently for this to complete.
+pub async fn discover_channel(handle: &ClientHandle, timeout: Duration) -> Result<u32, Error> {
+    tracing::trace!("discovering channel");
+
+    let probes = (0..=u8::MAX as u32)
+        .filter(|channel_id| addr::address_for_channel(*channel_id).is_some())
+        .map(|channel_id| probe_channel(handle.clone(), channel_id, timeout).boxed());
+
+    // Dropping the remaining probes will cancel the associated calls.
+    let (channel, _remaining) = futures::future::select_ok(probes).await?;
+
+    tracing::trace!(channel=channel, "channel discovered");
+    Ok(channel)
+}
+
+/// Find all active Maestro channels in the given range by probing them.
+///
+/// Like [`discover_channel`], this sends a `GetSoftwareInfo` request on each
+/// channel with a known address mapping, all at the same time, and waits up
+/// to `timeout` for each response. Channels without address mapping are
+/// skipped. Returns the channels that responded successfully, in ascending
+/// order. Channels not responding in time or responding with an error are
+/// considered inactive, whereas other errors, e.g. due to the client having
+/// been terminated, are returned.
+///
+/// Note that the client needs to be run concurrently for this to complete.
+pub async fn scan_channels(handle: &ClientHandle, range: RangeInclusive<u32>, timeout: Duration)
+    -> Result<Vec<u32>, Error>
+{
+    tracing::trace!("scanning channels {:?}", range);
+
+    let candidates: Vec<u32> = range
+        .filter(|channel_id| addr::address_for_channel(*channel_id).is_some())
+        .collect();
+
+    let probes = candidates.iter()
+        .map(|&channel_id| probe_channel(handle.clone(), channel_id, timeout));
+
+    let results = futures::future::join_all(probes).await;
+
+    let mut channels = Vec::new();
+
+    for (channel_id, result) in candidates.into_iter().zip(results) {
+        match result {
+            Ok(channel_id) => channels.push(channel_id),
+            Err(e) if e.is_remote() || e.code() == Status::DeadlineExceeded => {
+                tracing::trace!(error=?e, "no response on channel {}", channel_id);
+            },
+            Err(e) => return Err(e),
+        }
+    }
+
+    tracing::trace!(channels=?channels, "channels scanned");
+    Ok(channels)
+}
+
+async fn probe_channel(mut handle: ClientHandle, channel_id: u32, timeout: Duration) -> Result<u32, Error> {
+    let path = PathRef::new("maestro_pw.Maestro/GetSoftwareInfo");
+
+    let req = Request {
+        channel_id,
+        service_id: ServiceId(path.service().hash()),
+        method_id: MethodId(path.method().hash()),
+        call_id: CallId(0),
+        message: (),
+        deadline: Some(timeout),
+    };
+
+    let mut rsp: UnaryResponse<SoftwareInfo> = handle.call_unary(req)?;
+
+    rsp.result().await?;
+    Ok(channel_id)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::pwrpc::testing;
+    use crate::pwrpc::types::PacketType;
+
+    #[tokio::test]
+    async fn test_discover_channel() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let handle = client.handle();
+
+        let exchange = async {
+            let discover = discover_channel(&handle, Duration::from_secs(1));
+
+            let respond = async {
+                let mut channels = Vec::new();
+
+                for _ in 18..=27 {
+                    let request = device.recv().await.unwrap();
+                    assert_eq!(request.r#type, PacketType::Request as i32);
+
+                    channels.push(request.channel_id);
+
+                    if request.channel_id == 21 {
+                        device.send(testing::response(&request, &SoftwareInfo::default())).unwrap();
+                    }
+                }
+
+                channels.sort();
+                assert_eq!(channels, (18..=27).collect::<Vec<_>>());
+            };
+
+            let (channel, _) = futures::join!(discover, respond);
+
+            // all other probes should have been cancelled
+            let mut cancelled = Vec::new();
+            for _ in 0..9 {
+                let packet = device.recv().await.unwrap();
+
+                assert_eq!(packet.r#type, PacketType::ClientError as i32);
+                assert_eq!(packet.status, Status::Cancelled as u32);
+
+                cancelled.push(packet.channel_id);
+            }
+
+            cancelled.sort();
+            assert_eq!(cancelled, [18, 19, 20, 22, 23, 24, 25, 26, 27]);
+
+            channel
+        };
+
+        let channel = testing::run_with_client(&mut client, exchange).await;
+        assert_eq!(channel.unwrap(), 21);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_scan_channels() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let handle = client.handle();
+
+        let exchange = async {
+            // channels 0 to 17 have no address mapping and are skipped
+            let scan = scan_channels(&handle, 0..=23, Duration::from_secs(1));
+
+            let respond = async {
+                let mut requests = Vec::new();
+                for _ in 18..=23 {
+                    requests.push(device.recv().await.unwrap());
+                }
+
+                for request in requests {
+                    match request.channel_id {
+                        19 | 21 => {
+                            device.send(testing::response(&request, &SoftwareInfo::default())).unwrap();
+                        },
+                        22 => {
+                            device.send(testing::server_error(&request, Status::NotFound)).unwrap();
+                        },
+                        _ => {},
+                    }
+                }
+            };
+
+            futures::join!(scan, respond).0
+        };
+
+        let channels = testing::run_with_client(&mut client, exchange).await;
+        assert_eq!(channels.unwrap(), [19, 21]);
+    }
+}
@@ -0,0 +1,98 @@
+//! Structured firmware versions.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use super::types::FirmwareVersion;
+use crate::pwrpc::Error;
+
+
+/// Firmware version in `major.minor.patch` format, ordered numerically.
+///
+/// For example, `SemVer::new(2, 651, 0)` corresponds to version string
+/// `2.651.0` and is newer than `2.99.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SemVer {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl SemVer {
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+        Self { major, minor, patch }
+    }
+}
+
+impl FromStr for SemVer {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::invalid_argument(format!("invalid version string: '{s}'"));
+
+        let component = |c: &str| {
+            if c.is_empty() || !c.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            c.parse().map_err(|_| invalid())
+        };
+
+        let mut parts = s.split('.');
+
+        let (Some(major), Some(minor), Some(patch), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+
+        Ok(Self::new(component(major)?, component(minor)?, component(patch)?))
+    }
+}
+
+impl Display for SemVer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+
+impl FirmwareVersion {
+    /// Parse the version string of this firmware.
+    ///
+    /// Fails with `InvalidArgument` if the version string is not in
+    /// `major.minor.patch` format.
+    pub fn semver(&self) -> Result<SemVer, Error> {
+        self.version_string.parse()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::pwrpc::Status;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("2.651.0".parse::<SemVer>().unwrap(), SemVer::new(2, 651, 0));
+        assert_eq!(SemVer::new(2, 651, 0).to_string(), "2.651.0");
+
+        for s in ["", "2.651", "2.651.0.1", "2..0", "2.651.x", "+2.651.0", "2.651.0 ", "2.70000.0"] {
+            let err = s.parse::<SemVer>().unwrap_err();
+            assert_eq!(err.code(), Status::InvalidArgument, "{s:?}");
+        }
+
+        let fw = FirmwareVersion { version_string: "3.1.4".into(), ..Default::default() };
+        assert_eq!(fw.semver().unwrap(), SemVer::new(3, 1, 4));
+        assert!(FirmwareVersion::default().semver().is_err());
+    }
+
+    #[test]
+    fn test_ord() {
+        assert!(SemVer::new(2, 651, 0) > SemVer::new(2, 99, 0));
+        assert!(SemVer::new(2, 651, 0) >= SemVer::new(2, 651, 0));
+        assert!(SemVer::new(3, 0, 0) > SemVer::new(2, 651, 9));
+        assert!(SemVer::new(2, 651, 1) > SemVer::new(2, 651, 0));
+    }
+}
@@ -0,0 +1,339 @@
+//! Capture and replay of RPC packet streams for debugging.
+//!
+//! Captures are stored as newline-delimited JSON, with one [`Record`] per
+//! packet.
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::time::SystemTime;
+
+use futures::{Sink, Stream, StreamExt};
+
+use serde::{Deserialize, Serialize};
+
+use super::status::{Error, Status};
+use super::types::RpcPacket;
+
+
+/// Direction of a captured packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Received from the device.
+    Rx,
+
+    /// Sent to the device.
+    Tx,
+}
+
+/// A single captured packet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub direction: Direction,
+    pub packet: RpcPacket,
+
+    /// Time at which the packet has been captured, in milliseconds since the
+    /// Unix epoch. Optional, so that hand-written captures can omit it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_ms: Option<u64>,
+}
+
+
+/// Transport wrapper writing all sent and received packets to a capture.
+///
+/// Can be passed to [`Client::new`](super::client::Client::new) in place of
+/// the wrapped transport.
+#[derive(Debug)]
+pub struct PacketCapture<S, W> {
+    inner: S,
+    writer: W,
+}
+
+impl<S, W> PacketCapture<S, W>
+where
+    W: Write,
+{
+    pub fn new(inner: S, writer: W) -> Self {
+        Self { inner, writer }
+    }
+
+    pub fn into_inner(self) -> (S, W) {
+        (self.inner, self.writer)
+    }
+
+    fn write(&mut self, direction: Direction, packet: &RpcPacket) -> Result<(), Error> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|t| t.as_millis() as u64)
+            .ok();
+
+        let record = RecordRef { direction, packet, timestamp_ms };
+
+        serde_json::to_writer(&mut self.writer, &record)
+            .map_err(|e| Error::extend(Status::Internal, "failed to write capture", e))?;
+
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+impl<S, W, E> Stream for PacketCapture<S, W>
+where
+    S: Stream<Item = Result<RpcPacket, E>> + Unpin,
+    W: Write + Unpin,
+    Error: From<E>,
+{
+    type Item = Result<RpcPacket, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.inner.poll_next_unpin(cx) {
+            Poll::Ready(Some(Ok(packet))) => {
+                let res = self.write(Direction::Rx, &packet).map(|_| packet);
+                Poll::Ready(Some(res))
+            },
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e.into()))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<S, W> Sink<RpcPacket> for PacketCapture<S, W>
+where
+    S: Sink<RpcPacket> + Unpin,
+    W: Write + Unpin,
+    Error: From<S::Error>,
+{
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.inner).poll_ready(cx).map_err(Error::from)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: RpcPacket) -> Result<(), Error> {
+        self.write(Direction::Tx, &item)?;
+        Pin::new(&mut self.inner).start_send(item).map_err(Error::from)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.writer.flush()?;
+        Pin::new(&mut self.inner).poll_flush(cx).map_err(Error::from)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.writer.flush()?;
+        Pin::new(&mut self.inner).poll_close(cx).map_err(Error::from)
+    }
+}
+
+#[derive(Serialize)]
+struct RecordRef<'a> {
+    direction: Direction,
+    packet: &'a RpcPacket,
+    timestamp_ms: Option<u64>,
+}
+
+
+/// Read a capture and create a transport replaying it.
+pub fn replay_from_reader(reader: impl Read) -> Result<Replay, Error> {
+    let records = serde_json::Deserializer::from_reader(reader)
+        .into_iter::<Record>()
+        .collect::<Result<VecDeque<_>, _>>()
+        .map_err(|e| Error::extend(Status::InvalidArgument, "failed to parse capture", e))?;
+
+    Ok(Replay { records, waker: None })
+}
+
+
+/// Transport replaying a previously recorded capture.
+///
+/// Received packets are yielded in order, but only once all packets that had
+/// been sent before them in the capture have been sent again. Each sent
+/// packet is matched against the next sent packet of the capture, even if
+/// received packets preceding it have not been yielded yet. This allows
+/// clients to issue multiple calls at once, independent of how they had been
+/// interleaved with responses originally. Sent packets are not compared
+/// against the capture, apart from logging a warning on mismatch. Once the
+/// capture has been replayed completely, the transport behaves like an idle
+/// device, i.e., it does not yield any further packets but does not end
+/// either.
+#[derive(Debug)]
+pub struct Replay {
+    records: VecDeque<Record>,
+    waker: Option<Waker>,
+}
+
+impl Stream for Replay {
+    type Item = Result<RpcPacket, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.records.front() {
+            Some(record) if record.direction == Direction::Rx => {
+                let record = self.records.pop_front().unwrap();
+                Poll::Ready(Some(Ok(record.packet)))
+            },
+            Some(_) => {
+                // wait for the client to send the next packet
+                self.waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Sink<RpcPacket> for Replay {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: RpcPacket) -> Result<(), Error> {
+        match self.records.iter().position(|r| r.direction == Direction::Tx) {
+            Some(index) => {
+                let record = self.records.remove(index).unwrap();
+
+                if record.packet != item {
+                    tracing::warn!("sent packet does not match capture: expected {:?}, got {:?}",
+                                   record.packet, item);
+                }
+
+                if let Some(waker) = self.waker.take() {
+                    waker.wake();
+                }
+            },
+            None => {
+                tracing::warn!("sent packet not in capture: {:?}", item);
+            },
+        }
+
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use futures::SinkExt;
+
+    use super::*;
+
+    use crate::protocol::types::SoftwareInfo;
+    use crate::pwrpc::client::Client;
+    use crate::pwrpc::testing;
+    use crate::pwrpc::types::PacketType;
+    use crate::service::MaestroService;
+
+    #[tokio::test]
+    async fn test_capture_replay() {
+        let (stream, mut device) = testing::pair();
+        let mut capture = PacketCapture::new(stream, Vec::new());
+
+        let info = SoftwareInfo { unknown2: 42, ..Default::default() };
+
+        // capture a request and its response
+        let request = RpcPacket {
+            r#type: PacketType::Request.into(),
+            channel_id: 18,
+            service_id: 0x7ede71ea,     // maestro_pw.Maestro
+            method_id: 0x7199fa44,      // GetSoftwareInfo
+            payload: bytes::Bytes::new(),
+            status: Status::Ok.into(),
+            call_id: 1,                 // first call ID assigned by a client
+        };
+
+        capture.send(request.clone()).await.unwrap();
+        assert_eq!(device.recv().await, Some(request.clone()));
+
+        device.send(testing::response(&request, &info)).unwrap();
+        capture.next().await.unwrap().unwrap();
+
+        let (_, data) = capture.into_inner();
+
+        // replay it
+        let replay = replay_from_reader(&data[..]).unwrap();
+        assert_eq!(replay.records.len(), 2);
+        assert!(replay.records.iter().all(|r| r.timestamp_ms.is_some()));
+
+        let mut client = Client::new(replay);
+        let mut service = MaestroService::new(client.handle(), 18);
+
+        let result = testing::run_with_client(&mut client, service.get_software_info()).await;
+
+        assert_eq!(result.unwrap(), info);
+    }
+
+    #[tokio::test]
+    async fn test_replay_concurrent_calls() {
+        let request = |method_id, call_id| RpcPacket {
+            r#type: PacketType::Request.into(),
+            channel_id: 18,
+            service_id: 0x7ede71ea,     // maestro_pw.Maestro
+            method_id,
+            payload: bytes::Bytes::new(),
+            status: Status::Ok.into(),
+            call_id,
+        };
+
+        let record = |direction, packet| Record { direction, packet, timestamp_ms: None };
+
+        let info = SoftwareInfo { unknown2: 42, ..Default::default() };
+        let req1 = request(0x7199fa44, 1);    // GetSoftwareInfo
+        let req2 = request(0x7199fa44, 2);
+
+        // originally, the second call has only been issued after the first
+        // one completed
+        let records = [
+            record(Direction::Tx, req1.clone()),
+            record(Direction::Rx, testing::response(&req1, &info)),
+            record(Direction::Tx, req2.clone()),
+            record(Direction::Rx, testing::server_error(&req2, Status::NotFound)),
+        ];
+
+        let mut data = Vec::new();
+        for record in &records {
+            serde_json::to_writer(&mut data, record).unwrap();
+            data.push(b'\n');
+        }
+
+        let replay = replay_from_reader(&data[..]).unwrap();
+
+        let mut client = Client::new(replay);
+        let mut handle = client.handle();
+
+        let exchange = async {
+            let mut call = |req: &RpcPacket| {
+                let req = crate::pwrpc::client::Request {
+                    channel_id: req.channel_id,
+                    service_id: req.service_id.into(),
+                    method_id: req.method_id.into(),
+                    call_id: req.call_id.into(),
+                    message: (),
+                    deadline: None,
+                };
+
+                handle.call_unary::<(), SoftwareInfo>(req).unwrap()
+            };
+
+            // issue both calls at once
+            let mut rsp1 = call(&req1);
+            let mut rsp2 = call(&req2);
+
+            assert_eq!(rsp1.result().await.unwrap(), info);
+            assert_eq!(rsp2.result().await.unwrap_err().code(), Status::NotFound);
+        };
+
+        testing::run_with_client(&mut client, exchange).await;
+    }
+}
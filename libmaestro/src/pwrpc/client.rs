@@ -1,15 +1,22 @@
 use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::task::Poll;
+use std::time::Duration;
+
+use bytes::Bytes;
 
 use futures::{Sink, SinkExt, Stream, StreamExt};
 use futures::channel::mpsc;
-use futures::stream::{SplitSink, SplitStream, FusedStream};
+use futures::stream::{BoxStream, SplitSink, SplitStream, FusedStream};
 
 use prost::Message;
 
+use tokio::time::Instant;
+
 use super::id::Path;
-use super::status::{Status, Error};
-use super::types::{RpcType, RpcPacket, PacketType};
+use super::status::{Status, Error, Timeout};
+use super::types::{CallId, MethodId, RpcType, RpcPacket, PacketType, ServiceId};
 
 
 #[derive(Debug)]
@@ -29,6 +36,29 @@ pub struct Client<S> {
 
     /// Pending RPC calls, waiting for a response.
     pending: Vec<Call>,
+
+    /// Number of calls currently in flight, shared with all handles.
+    in_flight: Arc<CallCounter>,
+
+    /// Number of handles currently alive, shared with all handles.
+    handles: Arc<HandleCounter>,
+
+    /// Source of call IDs for new requests, shared with all handles.
+    call_ids: Arc<CallIds>,
+
+    /// Whether all handles have been dropped at some point, i.e., whether the
+    /// client should shut down once it is idle.
+    released: bool,
+
+    /// Whether the client has already been terminated.
+    terminated: bool,
+
+    /// Traffic counters, shared with anyone who requested them.
+    metrics: ClientMetrics,
+
+    /// Whether at least one packet has been received and processed, observed
+    /// by all handles.
+    ready: tokio::sync::watch::Sender<bool>,
 }
 
 impl<S, E> Client<S>
@@ -39,6 +69,14 @@ where
     Error: From<E>,
 {
     pub fn new(stream: S) -> Client<S> {
+        Self::with_capacity(stream, usize::MAX)
+    }
+
+    /// Create a new client, limiting the number of concurrent calls.
+    ///
+    /// Attempting to start or open more than `max_in_flight` calls at the same
+    /// time will fail with status `ResourceExhausted`.
+    pub fn with_capacity(stream: S, max_in_flight: usize) -> Client<S> {
         let (io_tx, io_rx) = stream.split();
         let (queue_tx, queue_rx) = mpsc::unbounded();
 
@@ -48,29 +86,73 @@ where
             queue_rx,
             queue_tx,
             pending: Vec::new(),
+            in_flight: Arc::new(CallCounter::new(max_in_flight)),
+            handles: Arc::new(HandleCounter::new()),
+            call_ids: Arc::new(CallIds::new()),
+            released: false,
+            terminated: false,
+            metrics: ClientMetrics::default(),
+            ready: tokio::sync::watch::channel(false).0,
         }
     }
 
     pub fn handle(&self) -> ClientHandle {
-        ClientHandle {
-            queue_tx: self.queue_tx.clone(),
-        }
+        ClientHandle::new(
+            self.queue_tx.clone(),
+            self.in_flight.clone(),
+            self.handles.clone(),
+            self.call_ids.clone(),
+            self.ready.subscribe(),
+        )
+    }
+
+    /// Return the number of handles to this client that are currently alive.
+    pub fn handle_count(&self) -> usize {
+        self.handles.count()
+    }
+
+    /// Return the traffic counters of this client.
+    ///
+    /// The counters are shared, i.e., they keep being updated while the
+    /// client runs and can be read from any thread without locking.
+    pub fn metrics(&self) -> ClientMetrics {
+        self.metrics.clone()
     }
 
+    /// Run the client, processing calls and incoming packets.
+    ///
+    /// This returns with an error once a fatal error has occurred, e.g. if the
+    /// underlying transport has been closed. The client should be terminated
+    /// afterwards via [`terminate`](Self::terminate).
+    ///
+    /// Once all handles have been dropped and no calls are pending any more,
+    /// nothing can be done with the client. In that case, it terminates itself
+    /// and this returns the result of doing so.
     pub async fn run(&mut self) -> Result<(), Error> {
         // Process the request queue first in case we are trying to catch some
         // early RPC responses via open() calls.
-        while let Ok(Some(request)) = self.queue_rx.try_next() {
+        while let Ok(request) = self.queue_rx.try_recv() {
             self.process_request(request).await?;
         }
 
         loop {
+            if self.released && self.is_idle() {
+                tracing::debug!("all client handles dropped, shutting down");
+                return self.terminate().await;
+            }
+
+            let deadline = self.next_deadline();
+            let handles = self.handles.clone();
+
             tokio::select! {
                 packet = self.io_rx.next() => {
                     let packet = packet
                         .ok_or_else(|| Error::aborted("underlying IO stream closed"))??;
 
+                    self.metrics.add_received(&packet);
                     self.process_packet(packet).await?;
+
+                    self.ready.send_if_modified(|ready| !std::mem::replace(ready, true));
                 },
                 request = self.queue_rx.next() => {
                     // SAFETY: We hold both sender and receiver parts and are
@@ -80,12 +162,59 @@ where
 
                     self.process_request(request).await?;
                 },
+                _ = sleep_until(deadline) => {
+                    self.process_deadlines().await?;
+                },
+                _ = handles.released.notified() => {
+                    self.released = true;
+                },
             }
         }
     }
 
+    /// Run the client, passing the fatal error that stopped it to `handler`.
+    ///
+    /// This allows running the client as part of a larger future, e.g. via
+    /// `tokio::select!`, without having to spawn a separate task just to log
+    /// its error.
+    pub async fn run_with_error_handler<F>(&mut self, handler: F)
+    where
+        F: FnOnce(Error),
+    {
+        if let Err(e) = self.run().await {
+            handler(e);
+        }
+    }
+
+    /// Terminate the client, bounding the time spent doing so by `timeout`.
+    ///
+    /// Like [`terminate`](Self::terminate), this aborts all pending calls,
+    /// sends cancellation packets for them to the peer, and closes the
+    /// underlying transport, which flushes any packets still buffered.
+    /// The protocol does not acknowledge cancellations, so once the transport
+    /// has been closed there is nothing left to wait for.
+    ///
+    /// Fails with [`Status::DeadlineExceeded`] if this could not be done
+    /// within `timeout`, e.g. because the transport is stalled. In that case,
+    /// the transport may not have been closed and is closed once the client
+    /// is dropped.
+    pub async fn graceful_shutdown(&mut self, timeout: Duration) -> Result<(), Error> {
+        match tokio::time::timeout(timeout, self.terminate()).await {
+            Ok(result) => result,
+            Err(_) => {
+                tracing::warn!("client did not shut down within {:?}", timeout);
+                Err(Error::deadline_exceeded("timed out while shutting down client"))
+            },
+        }
+    }
+
     pub async fn terminate(&mut self) -> Result<(), Error> {
+        if self.terminated {
+            return Ok(());
+        }
+
         tracing::trace!("terminating client");
+        self.terminated = true;
 
         // Collect messages to be sent instead of directly sending them. We
         // process infallible (local) operations first, before we try to
@@ -98,13 +227,14 @@ where
         // Process all pending requests. Abort requests for new calls and
         // send/forward any errors.
         //
-        // SAFETY: try_next() can only return an error when the channel has not
-        // been closed yet.
-        while let Some(msg) = self.queue_rx.try_next().unwrap() {
+        // Note: try_recv() can only return an empty-error when the channel has
+        // not been closed yet. Since we have closed it above, it will only
+        // return an error once all messages have been processed.
+        while let Ok(msg) = self.queue_rx.try_recv() {
             match msg {
                 CallRequest::New { sender, .. } => {
                     // Drop new requests. Instead, notify caller with status 'aborted'.
-                    let update = CallUpdate::Error { status: Status::Aborted };
+                    let update = CallUpdate::error(Status::Aborted);
                     let _ = sender.unbounded_send(update);
                     sender.close_channel();
                 },
@@ -118,6 +248,10 @@ where
                         }
                     }
                 },
+                CallRequest::StreamItem { .. } | CallRequest::StreamEnd { .. } | CallRequest::Cancel { .. } => {
+                    // Drop client stream messages and cancel requests. The
+                    // associated calls will be cancelled below.
+                },
             }
         }
 
@@ -164,16 +298,19 @@ where
         res_close
     }
 
+    #[tracing::instrument(level = "trace", name = "packet", skip_all, fields(
+        channel_id = packet.channel_id,
+        service_id = packet.service_id,
+        method_id = packet.method_id,
+        call_id = packet.call_id,
+    ))]
     async fn process_packet(&mut self, packet: RpcPacket) -> Result<(), Error> {
         tracing::trace!(
             "received packet: type=0x{:02x}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
             packet.r#type, packet.channel_id, packet.service_id, packet.method_id, packet.call_id
         );
 
-        let ty = packet.r#type;
-        let ty = PacketType::from_i32(ty);
-
-        match ty {
+        match packet.packet_type() {
             Some(PacketType::Response) => {
                 self.rpc_complete(packet).await
             },
@@ -183,10 +320,10 @@ where
             Some(PacketType::ServerStream) => {
                 self.rpc_stream_push(packet).await?
             },
-            Some(_) => {
+            Some(ty) => {
                 tracing::error!(
-                    "unsupported packet type: type=0x{:02x}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
-                    packet.r#type, packet.channel_id, packet.service_id, packet.method_id, packet.call_id
+                    "unsupported packet type: type={}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+                    ty, packet.channel_id, packet.service_id, packet.method_id, packet.call_id
                 );
             },
             None => {
@@ -219,7 +356,7 @@ where
                 }
 
                 let status = Status::from(packet.status);
-                call.complete(packet.payload, status).await;
+                call.complete(packet.payload, status, true).await;
             },
             None => {               // no pending call found, silently drop packet
                 tracing::debug!(
@@ -242,7 +379,7 @@ where
                 );
 
                 let status = Status::from(packet.status);
-                call.complete_with_error(status).await;
+                call.complete_with_remote_error(status).await;
             },
             None => {               // no pending call found, silently drop packet
                 tracing::debug!(
@@ -294,17 +431,62 @@ where
         Ok(())
     }
 
+    async fn process_deadlines(&mut self) -> Result<(), Error> {
+        let now = Instant::now();
+
+        let (expired, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|call| call.deadline.is_some_and(|deadline| deadline <= now));
+
+        self.pending = pending;
+
+        for mut call in expired {
+            tracing::debug!(
+                "rpc deadline exceeded: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+                call.uid.channel, call.uid.service, call.uid.method, call.uid.call,
+            );
+
+            let timeout = Timeout {
+                call_id: CallId(call.uid.call),
+                elapsed: now.duration_since(call.started),
+            };
+
+            call.complete_with(timeout.into()).await;
+            if call.tx {
+                self.send_client_error(call.uid, Status::Cancelled).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_idle(&self) -> bool {
+        self.handles.count() == 0 && self.pending.is_empty()
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.pending.iter()
+            .filter_map(|call| call.deadline)
+            .min()
+    }
+
+    #[tracing::instrument(level = "trace", name = "request", skip_all, fields(
+        channel_id = request.uid().channel,
+        service_id = request.uid().service,
+        method_id = request.uid().method,
+        call_id = request.uid().call,
+    ))]
     async fn process_request(&mut self, request: CallRequest) -> Result<(), Error> {
         match request {
-            CallRequest::New { ty, uid, payload, sender, tx } => {
-                let call = Call { ty, uid, sender };
+            CallRequest::New { ty, uid, payload, sender, tx, started, deadline, slot, completed } => {
+                let call = Call { ty, uid, sender, started, deadline, _slot: slot, tx, completed };
 
                 let packet = RpcPacket {
                     r#type: PacketType::Request.into(),
                     channel_id: uid.channel,
                     service_id: uid.service,
                     method_id: uid.method,
-                    payload,
+                    payload: payload.into(),
                     status: Status::Ok as _,
                     call_id: uid.call,
                 };
@@ -317,7 +499,7 @@ where
 
                 self.pending.push(call);
                 if tx {
-                    self.send(packet).await?;
+                    self.send_packet(packet).await?;
                 }
 
                 Ok(())
@@ -346,7 +528,72 @@ where
                     },
                 }
             },
+            CallRequest::StreamItem { uid, payload } => {
+                self.send_client_stream(uid, PacketType::ClientStream, payload).await
+            },
+            CallRequest::StreamEnd { uid } => {
+                self.send_client_stream(uid, PacketType::ClientStreamEnd, Vec::new()).await
+            },
+            CallRequest::Cancel { uid } => {
+                match self.find_and_remove_call(uid) {
+                    Some(mut call) => {
+                        tracing::trace!(
+                            "cancelling active rpc: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+                            uid.channel, uid.service, uid.method, uid.call,
+                        );
+
+                        // Complete instead of error to gracefully end streams.
+                        call.complete(Bytes::new(), Status::Cancelled, false).await;
+                        self.send_client_error(uid, Status::Cancelled).await
+                    },
+                    None => {
+                        tracing::trace!(
+                            "received cancel request for non-pending rpc: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+                            uid.channel, uid.service, uid.method, uid.call,
+                        );
+                        Ok(())
+                    },
+                }
+            },
+        }
+    }
+
+    async fn send_client_stream(&mut self, uid: CallUid, ty: PacketType, payload: Vec<u8>) -> Result<(), Error> {
+        let call = match self.find_call_mut(uid) {
+            Some(call) => call,
+            None => {
+                tracing::trace!(
+                    "received client stream request for non-pending rpc: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+                    uid.channel, uid.service, uid.method, uid.call,
+                );
+                return Ok(());
+            },
+        };
+
+        if !call.ty.has_client_stream() {
+            tracing::warn!(
+                "received client stream request for non-stream rpc: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+                uid.channel, uid.service, uid.method, uid.call,
+            );
+            return Ok(());
         }
+
+        tracing::trace!(
+            "sending client stream packet: type={:?}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+            ty, uid.channel, uid.service, uid.method, uid.call,
+        );
+
+        let packet = RpcPacket {
+            r#type: ty.into(),
+            channel_id: uid.channel,
+            service_id: uid.service,
+            method_id: uid.method,
+            call_id: uid.call,
+            payload: payload.into(),
+            status: Status::Ok as _,
+        };
+
+        self.send_packet(packet).await
     }
 
     fn find_and_remove_call(&mut self, uid: CallUid) -> Option<Call> {
@@ -376,32 +623,196 @@ where
             service_id: uid.service,
             method_id: uid.method,
             call_id: uid.call,
-            payload: Vec::new(),
+            payload: Bytes::new(),
             status,
         };
 
-        self.send(error_packet).await
+        self.send_packet(error_packet).await
     }
 
-    async fn send(&mut self, packet: RpcPacket) -> Result<(), Error> {
+    async fn send_packet(&mut self, packet: RpcPacket) -> Result<(), Error> {
+        let len = packet.encoded_len();
+
         self.io_tx.send(packet).await?;
+        self.metrics.add_sent(len);
         Ok(())
     }
 }
 
+/// Sink for sending raw packets to the peer, e.g. for injecting packets in
+/// tests.
+///
+/// Packets are passed to the transport as-is, without going through the
+/// request queue, and do not change the state of any call. Packets not
+/// belonging to a pending call are sent as well, but a warning is logged for
+/// them. As both require exclusive access, packets can only be sent this way
+/// while the client is not running. Closing the sink closes the transport.
+impl<S, E> Sink<RpcPacket> for Client<S>
+where
+    S: Sink<RpcPacket>,
+    S: Stream<Item = Result<RpcPacket, E>> + Unpin,
+    Error: From<S::Error>,
+    Error: From<E>,
+{
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.get_mut().io_tx).poll_ready(cx).map_err(Error::from)
+    }
+
+    fn start_send(self: Pin<&mut Self>, packet: RpcPacket) -> Result<(), Error> {
+        let this = self.get_mut();
 
-#[derive(Debug, Clone)]
+        let uid = CallUid::new(packet.channel_id, packet.service_id, packet.method_id, packet.call_id);
+        if !this.pending.iter().any(|call| call.uid == uid) {
+            tracing::warn!(
+                "sending packet not belonging to any pending call: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+                uid.channel, uid.service, uid.method, uid.call,
+            );
+        }
+
+        let len = packet.encoded_len();
+
+        Pin::new(&mut this.io_tx).start_send(packet)?;
+        this.metrics.add_sent(len);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.get_mut().io_tx).poll_flush(cx).map_err(Error::from)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.get_mut().io_tx).poll_close(cx).map_err(Error::from)
+    }
+}
+
+fn broadcast_stream<T>(rx: tokio::sync::broadcast::Receiver<T>) -> BoxStream<'static, T>
+where
+    T: Clone + Send + 'static,
+{
+    use tokio::sync::broadcast::error::RecvError;
+
+    futures::stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(item) => return Some((item, rx)),
+                Err(RecvError::Lagged(n)) => {
+                    tracing::warn!("stream consumer lagging behind, dropped {} items", n);
+                },
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }).boxed()
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => futures::future::pending().await,
+    }
+}
+
+
+/// Handle for initiating calls on a [`Client`].
+///
+/// Handles are cheap to clone, as they only consist of a queue sender and
+/// reference-counted shared state, and can be sent to and shared between
+/// threads. All clones refer to the same client, which shuts down once all of
+/// them have been dropped (see [`Client::run`]).
+#[derive(Debug)]
 pub struct ClientHandle {
     queue_tx: mpsc::UnboundedSender<CallRequest>,
+    in_flight: Arc<CallCounter>,
+    handles: Arc<HandleCounter>,
+    call_ids: Arc<CallIds>,
+    ready: tokio::sync::watch::Receiver<bool>,
 }
 
 impl ClientHandle {
+    fn new(
+        queue_tx: mpsc::UnboundedSender<CallRequest>,
+        in_flight: Arc<CallCounter>,
+        handles: Arc<HandleCounter>,
+        call_ids: Arc<CallIds>,
+        ready: tokio::sync::watch::Receiver<bool>,
+    ) -> Self {
+        handles.count.fetch_add(1, Ordering::AcqRel);
+        ClientHandle { queue_tx, in_flight, handles, call_ids, ready }
+    }
+
+    /// Wait until the client is running and has processed at least one
+    /// packet received from the device.
+    ///
+    /// Packets sent by the device right after connecting, e.g. responses to
+    /// calls of a previous session, are only caught if corresponding calls
+    /// have been opened before (see [`open_unary`](Self::open_unary)). This
+    /// allows waiting for the first of them before starting new calls.
+    ///
+    /// Fails with `DeadlineExceeded` if no packet has been processed within
+    /// the given timeout, which is also the case if the device does not send
+    /// anything on its own, and with `Aborted` if the client has been
+    /// dropped.
+    pub async fn wait_ready(&self, timeout: Duration) -> Result<(), Error> {
+        let mut ready = self.ready.clone();
+        let wait = async move { ready.wait_for(|ready| *ready).await.map(|_| ()) };
+
+        match tokio::time::timeout(timeout, wait).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(_)) => Err(Error::aborted("client has been dropped")),
+            Err(_) => Err(Error::deadline_exceeded("no packet received from device")),
+        }
+    }
+
+    /// Return a new call ID, unique among the calls of this client until the
+    /// ID space wraps around.
+    ///
+    /// IDs are never zero, which is left for calls whose ID is not known in
+    /// advance, e.g. calls opened to catch responses of previous sessions.
+    pub fn next_call_id(&self) -> CallId {
+        CallId(self.call_ids.next())
+    }
+
+    /// Create a builder for a request with the given message, assigning it a
+    /// new call ID unless set explicitly.
+    pub fn request<M>(&self, message: M) -> RequestBuilder<M> {
+        RequestBuilder::new(self.call_ids.clone(), message)
+    }
+
+    /// Return the number of calls that are currently in flight, i.e., that
+    /// have been started or opened but not yet completed.
+    pub fn in_flight_count(&self) -> usize {
+        self.in_flight.count()
+    }
+
+    /// Call the given unary method and wait for its response.
+    ///
+    /// Service and method IDs as well as request and response types are taken
+    /// from the method definition. Use [`call_unary`](Self::call_unary) for
+    /// methods without such a definition.
+    pub async fn call<M>(&mut self, channel_id: u32, message: M::Request) -> Result<M::Response, Error>
+    where
+        M: RpcMethod,
+    {
+        let req = Request {
+            channel_id,
+            service_id: ServiceId(M::SERVICE_ID),
+            method_id: MethodId(M::METHOD_ID),
+            call_id: self.next_call_id(),
+            message,
+            deadline: None,
+        };
+
+        self.call_unary::<M::Request, M::Response>(req)?
+            .result().await
+    }
+
     pub fn call_unary<M1, M2>(&mut self, request: Request<M1>) -> Result<UnaryResponse<M2>, Error>
     where
         M1: Message,
         M2: Message + Default,
     {
-        let handle = self.call(RpcType::Unary, request)?;
+        let handle = self.start(RpcType::Unary, request)?;
 
         let response = UnaryResponse {
             maker: std::marker::PhantomData,
@@ -411,12 +822,51 @@ impl ClientHandle {
         Ok(response)
     }
 
+    /// Call a unary method and wait for its response, retrying failed
+    /// attempts according to the given policy.
+    ///
+    /// The request is created anew by `req_factory` for each attempt, which
+    /// should assign it a new call ID, e.g. via [`request`](Self::request).
+    /// Errors returned by the factory and errors not accepted by
+    /// [`RetryPolicy::retry_on`] are returned immediately. Once all attempts
+    /// have failed, the error of the last attempt is returned.
+    pub async fn retry_unary<M1, M2, F>(&mut self, mut req_factory: F, policy: RetryPolicy) -> Result<M2, Error>
+    where
+        M1: Message,
+        M2: Message + Default,
+        F: FnMut(&ClientHandle) -> Result<Request<M1>, Error>,
+    {
+        let mut delay = policy.initial_delay;
+        let mut attempt = 1;
+
+        loop {
+            let request = req_factory(self)?;
+
+            let err = match self.call_unary::<M1, M2>(request)?.result().await {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+
+            if attempt >= policy.max_attempts || !(policy.retry_on)(&err) {
+                return Err(err);
+            }
+
+            tracing::debug!(error=?err, "call failed, trying again in {:?} ({}/{})",
+                            delay, attempt, policy.max_attempts);
+
+            tokio::time::sleep(delay).await;
+
+            delay = delay.saturating_mul(2);
+            attempt += 1;
+        }
+    }
+
     pub fn call_server_stream<M1, M2>(&mut self, request: Request<M1>) -> Result<StreamResponse<M2>, Error>
     where
         M1: Message,
         M2: Message + Default,
     {
-        let handle = self.call(RpcType::ServerStream, request)?;
+        let handle = self.start(RpcType::ServerStream, request)?;
 
         let stream = StreamResponse {
             marker: std::marker::PhantomData,
@@ -426,24 +876,70 @@ impl ClientHandle {
         Ok(stream)
     }
 
-    fn call<M>(&mut self, ty: RpcType, request: Request<M>) -> Result<CallHandle, Error>
+    /// Start a bidirectional streaming call.
+    ///
+    /// The message of the request is sent as payload of the initial request
+    /// packet. Further messages can be sent via [`BidiStreamResponse::sink`].
+    pub fn call_bidi_stream<M1, M2>(&mut self, request: Request<M1>) -> Result<BidiStreamResponse<M1, M2>, Error>
+    where
+        M1: Message,
+        M2: Message + Default,
+    {
+        let handle = self.start(RpcType::BidirectionalStream, request)?;
+
+        let stream = BidiStreamResponse {
+            marker1: std::marker::PhantomData,
+            marker2: std::marker::PhantomData,
+            handle,
+        };
+
+        Ok(stream)
+    }
+
+    /// Cancel the call with the given ID.
+    ///
+    /// Sends a cancellation to the server and completes the call. After this,
+    /// unary calls return status `Cancelled` and streams end without further
+    /// items. Cancelling a call that is not pending has no effect.
+    pub fn cancel(&mut self, uid: CallUid) -> Result<(), Error> {
+        self.queue_tx.unbounded_send(CallRequest::Cancel { uid })
+            .map_err(|_| Error::aborted("the channel has been closed, calls cannot be cancelled"))
+    }
+
+    fn start<M>(&mut self, ty: RpcType, request: Request<M>) -> Result<CallHandle, Error>
     where
         M: Message,
     {
+        let slot = self.in_flight.acquire()
+            .ok_or_else(|| Error::resource_exhausted("too many calls in flight"))?;
+
         let (sender, receiver) = mpsc::unbounded();
 
         let uid = CallUid {
             channel: request.channel_id,
-            service: request.service_id,
-            method: request.method_id,
-            call: request.call_id,
+            service: request.service_id.into(),
+            method: request.method_id.into(),
+            call: request.call_id.into(),
         };
 
+        // Note: The message is only formatted if trace-level logging is
+        // enabled. All messages implement Debug via prost::Message.
+        tracing::trace!(
+            "queueing rpc: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, message={:?}",
+            uid.channel, uid.service, uid.method, uid.call, request.message,
+        );
+
         let payload = request.message.encode_to_vec();
+        let started = Instant::now();
+        let deadline = request.deadline.map(|timeout| started + timeout);
         let queue_tx = self.queue_tx.clone();
 
-        let request = CallRequest::New { ty, uid, payload, sender, tx: true };
-        let handle = CallHandle { uid, queue_tx, receiver, cancel_on_drop: true };
+        let completed = Arc::new(AtomicBool::new(false));
+
+        let request = CallRequest::New {
+            ty, uid, payload, sender, tx: true, started, deadline, slot, completed: completed.clone(),
+        };
+        let handle = CallHandle { uid, queue_tx, receiver, cancel_on_drop: true, completed };
 
         self.queue_tx.unbounded_send(request)
             .map_err(|_| Error::aborted("the channel has been closed, no new calls are allowed"))?;
@@ -483,20 +979,29 @@ impl ClientHandle {
     where
         M: Message,
     {
+        let slot = self.in_flight.acquire()
+            .ok_or_else(|| Error::resource_exhausted("too many calls in flight"))?;
+
         let (sender, receiver) = mpsc::unbounded();
 
         let uid = CallUid {
             channel: request.channel_id,
-            service: request.service_id,
-            method: request.method_id,
-            call: request.call_id,
+            service: request.service_id.into(),
+            method: request.method_id.into(),
+            call: request.call_id.into(),
         };
 
         let payload = Vec::new();
+        let started = Instant::now();
+        let deadline = request.deadline.map(|timeout| started + timeout);
         let queue_tx = self.queue_tx.clone();
 
-        let request = CallRequest::New { ty, uid, payload, sender, tx: false };
-        let handle = CallHandle { uid, queue_tx, receiver, cancel_on_drop: false };
+        let completed = Arc::new(AtomicBool::new(false));
+
+        let request = CallRequest::New {
+            ty, uid, payload, sender, tx: false, started, deadline, slot, completed: completed.clone(),
+        };
+        let handle = CallHandle { uid, queue_tx, receiver, cancel_on_drop: false, completed };
 
         self.queue_tx.unbounded_send(request)
             .map_err(|_| Error::aborted("the channel has been closed, no new calls are allowed"))?;
@@ -506,8 +1011,30 @@ impl ClientHandle {
 }
 
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct CallUid {
+impl Clone for ClientHandle {
+    fn clone(&self) -> Self {
+        ClientHandle::new(
+            self.queue_tx.clone(),
+            self.in_flight.clone(),
+            self.handles.clone(),
+            self.call_ids.clone(),
+            self.ready.clone(),
+        )
+    }
+}
+
+impl Drop for ClientHandle {
+    fn drop(&mut self) {
+        if self.handles.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.handles.released.notify_one();
+        }
+    }
+}
+
+
+/// Unique identifier of a call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallUid {
     channel: u32,
     service: u32,
     method: u32,
@@ -515,6 +1042,31 @@ struct CallUid {
 }
 
 impl CallUid {
+    pub fn new(channel_id: u32, service_id: u32, method_id: u32, call_id: u32) -> Self {
+        Self {
+            channel: channel_id,
+            service: service_id,
+            method: method_id,
+            call: call_id,
+        }
+    }
+
+    pub fn channel_id(&self) -> u32 {
+        self.channel
+    }
+
+    pub fn service_id(&self) -> u32 {
+        self.service
+    }
+
+    pub fn method_id(&self) -> u32 {
+        self.method
+    }
+
+    pub fn call_id(&self) -> u32 {
+        self.call
+    }
+
     fn from_packet(packet: &RpcPacket) -> Self {
         Self {
             channel: packet.channel_id,
@@ -534,80 +1086,283 @@ enum CallRequest {
         payload: Vec<u8>,
         sender: mpsc::UnboundedSender<CallUpdate>,
         tx: bool,
+        started: Instant,
+        deadline: Option<Instant>,
+        slot: CallSlot,
+        completed: Arc<AtomicBool>,
     },
     Error {
         uid: CallUid,
         code: Status,
         tx: bool,
     },
+    StreamItem {
+        uid: CallUid,
+        payload: Vec<u8>,
+    },
+    StreamEnd {
+        uid: CallUid,
+    },
+    Cancel {
+        uid: CallUid,
+    },
+}
+
+impl CallRequest {
+    fn uid(&self) -> CallUid {
+        match self {
+            CallRequest::New { uid, .. }
+            | CallRequest::Error { uid, .. }
+            | CallRequest::StreamItem { uid, .. }
+            | CallRequest::StreamEnd { uid }
+            | CallRequest::Cancel { uid } => *uid,
+        }
+    }
 }
 
 
 #[derive(Debug)]
 enum CallUpdate {
     Complete {
-        data: Vec<u8>,
+        data: Bytes,
         status: Status,
+        remote: bool,
     },
     StreamItem {
-        data: Vec<u8>,
+        data: Bytes,
     },
     Error {
-        status: Status,
+        error: Error,
+    }
+}
+
+impl CallUpdate {
+    fn error(status: Status) -> Self {
+        CallUpdate::Error { error: Error::from(status) }
+    }
+}
+
+fn status_error(status: Status, remote: bool) -> Error {
+    if remote {
+        Error::remote(status)
+    } else {
+        Error::from(status)
     }
 }
 
 
 #[derive(Debug)]
-struct Call {
-    ty: RpcType,
-    uid: CallUid,
-    sender: mpsc::UnboundedSender<CallUpdate>,
+struct CallCounter {
+    count: AtomicUsize,
+    limit: usize,
 }
 
-impl Call {
-    pub async fn complete(&mut self, payload: Vec<u8>, status: Status) {
-        let update = CallUpdate::Complete { data: payload, status };
-        self.push_update(update).await;
-        self.sender.close_channel();
+impl CallCounter {
+    fn new(limit: usize) -> Self {
+        Self { count: AtomicUsize::new(0), limit }
     }
 
-    pub async fn complete_with_error(&mut self, status: Status) {
-        let update = CallUpdate::Error { status };
-        self.push_update(update).await;
-        self.sender.close_channel();
+    fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
     }
 
-    pub async fn push_item(&mut self, payload: Vec<u8>) {
-        let update = CallUpdate::StreamItem { data: payload };
-        self.push_update(update).await;
+    fn acquire(self: &Arc<Self>) -> Option<CallSlot> {
+        self.count
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| (n < self.limit).then_some(n + 1))
+            .ok()?;
+
+        Some(CallSlot { counter: self.clone() })
     }
+}
 
-    async fn push_update(&mut self, update: CallUpdate) {
-        if let Err(e) = self.sender.unbounded_send(update) {
-            let update = e.into_inner();
 
-            match update {
-                CallUpdate::Complete { .. } => {
-                    tracing::warn!(
-                        "cannot send call update, caller is gone: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, update=complete",
-                        self.uid.channel, self.uid.service, self.uid.method, self.uid.call,
-                    )
-                },
-                CallUpdate::StreamItem { .. } => {
-                    tracing::warn!(
-                        "cannot send call update, caller is gone: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, update=stream",
-                        self.uid.channel, self.uid.service, self.uid.method, self.uid.call,
-                    )
-                },
-                CallUpdate::Error { status } => {
-                    let code: u32 = status.into();
+/// Policy for retrying failed calls via [`ClientHandle::retry_unary`].
+///
+/// The delay between two attempts starts at `initial_delay` and is doubled
+/// after each failed attempt. Only errors for which `retry_on` returns `true`
+/// are retried. By default, these are errors caused by the transport (see
+/// [`is_transport_error`](Self::is_transport_error)), whereas errors reported
+/// by the RPC peer are returned immediately.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub retry_on: fn(&Error) -> bool,
+}
 
-                    tracing::trace!(
-                        "cannot send call update, caller is gone: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, update=error, error={}",
-                        self.uid.channel, self.uid.service, self.uid.method, self.uid.call, code,
-                    )
-                },
+impl RetryPolicy {
+    /// Whether the given error has been caused locally by the transport,
+    /// i.e., is an IO error, a connection failure, or a timeout, rather than
+    /// having been reported by the RPC peer.
+    pub fn is_transport_error(err: &Error) -> bool {
+        if err.is_remote() {
+            return false;
+        }
+
+        let io = std::error::Error::source(err)
+            .is_some_and(|source| source.is::<std::io::Error>());
+
+        io || matches!(err.code(), Status::Unavailable | Status::DeadlineExceeded)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(100),
+            retry_on: Self::is_transport_error,
+        }
+    }
+}
+
+
+/// Traffic counters of a [`Client`].
+///
+/// Byte counts refer to the encoded RPC packets, i.e., they do not include
+/// any framing added by the transport (see
+/// [`CodecStats`](crate::hdlc::CodecStats) for that). Counters are updated
+/// with relaxed ordering, so values read concurrently are approximate.
+#[derive(Debug, Clone, Default)]
+pub struct ClientMetrics {
+    pub bytes_sent: Arc<AtomicU64>,
+    pub bytes_received: Arc<AtomicU64>,
+    pub packets_sent: Arc<AtomicU64>,
+    pub packets_received: Arc<AtomicU64>,
+}
+
+impl ClientMetrics {
+    fn add_sent(&self, len: usize) {
+        self.packets_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    fn add_received(&self, packet: &RpcPacket) {
+        self.packets_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received.fetch_add(packet.encoded_len() as u64, Ordering::Relaxed);
+    }
+}
+
+
+#[derive(Debug)]
+struct HandleCounter {
+    count: AtomicUsize,
+    released: tokio::sync::Notify,
+}
+
+impl HandleCounter {
+    fn new() -> Self {
+        Self { count: AtomicUsize::new(0), released: tokio::sync::Notify::new() }
+    }
+
+    fn count(&self) -> usize {
+        self.count.load(Ordering::Acquire)
+    }
+}
+
+
+#[derive(Debug)]
+struct CallIds {
+    next: AtomicU32,
+}
+
+impl CallIds {
+    fn new() -> Self {
+        Self { next: AtomicU32::new(1) }
+    }
+
+    fn next(&self) -> u32 {
+        loop {
+            match self.next.fetch_add(1, Ordering::Relaxed) {
+                0 => continue,      // skip zero after wrapping around
+                id => return id,
+            }
+        }
+    }
+}
+
+
+/// Slot in the table of in-flight calls, released on drop.
+#[derive(Debug)]
+struct CallSlot {
+    counter: Arc<CallCounter>,
+}
+
+impl Drop for CallSlot {
+    fn drop(&mut self) {
+        self.counter.count.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+
+#[derive(Debug)]
+struct Call {
+    ty: RpcType,
+    uid: CallUid,
+    sender: mpsc::UnboundedSender<CallUpdate>,
+    started: Instant,
+    deadline: Option<Instant>,
+    _slot: CallSlot,
+
+    /// Whether the request has been sent to the peer, i.e., whether the call
+    /// is known to the peer and needs to be cancelled there.
+    tx: bool,
+
+    /// Completion flag, shared with the caller. Set once the call has been
+    /// removed from the list of pending calls.
+    completed: Arc<AtomicBool>,
+}
+
+impl Call {
+    pub async fn complete(&mut self, payload: Bytes, status: Status, remote: bool) {
+        let update = CallUpdate::Complete { data: payload, status, remote };
+        self.push_update(update).await;
+        self.sender.close_channel();
+    }
+
+    pub async fn complete_with_error(&mut self, status: Status) {
+        self.complete_with(Error::from(status)).await;
+    }
+
+    pub async fn complete_with_remote_error(&mut self, status: Status) {
+        self.complete_with(Error::remote(status)).await;
+    }
+
+    async fn complete_with(&mut self, error: Error) {
+        self.push_update(CallUpdate::Error { error }).await;
+        self.sender.close_channel();
+    }
+
+    pub async fn push_item(&mut self, payload: Bytes) {
+        let update = CallUpdate::StreamItem { data: payload };
+        self.push_update(update).await;
+    }
+
+    async fn push_update(&mut self, update: CallUpdate) {
+        if let Err(e) = self.sender.unbounded_send(update) {
+            let update = e.into_inner();
+
+            match update {
+                CallUpdate::Complete { .. } => {
+                    tracing::warn!(
+                        "cannot send call update, caller is gone: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, update=complete",
+                        self.uid.channel, self.uid.service, self.uid.method, self.uid.call,
+                    )
+                },
+                CallUpdate::StreamItem { .. } => {
+                    tracing::warn!(
+                        "cannot send call update, caller is gone: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, update=stream",
+                        self.uid.channel, self.uid.service, self.uid.method, self.uid.call,
+                    )
+                },
+                CallUpdate::Error { error } => {
+                    let code: u32 = error.code().into();
+
+                    tracing::trace!(
+                        "cannot send call update, caller is gone: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, update=error, error={}",
+                        self.uid.channel, self.uid.service, self.uid.method, self.uid.call, code,
+                    )
+                },
             }
         }
     }
@@ -615,10 +1370,12 @@ impl Call {
 
 impl Drop for Call {
     fn drop(&mut self) {
+        self.completed.store(true, Ordering::Release);
+
         // Notify caller that call has been aborted if the call has not been
         // completed yet. Ignore errors.
         if !self.sender.is_closed() {
-            let update = CallUpdate::Error { status: Status::Aborted };
+            let update = CallUpdate::error(Status::Aborted);
             let _ = self.sender.unbounded_send(update);
             self.sender.close_channel();
         }
@@ -631,6 +1388,7 @@ struct CallHandle {
     queue_tx: mpsc::UnboundedSender<CallRequest>,
     receiver: mpsc::UnboundedReceiver<CallUpdate>,
     cancel_on_drop: bool,
+    completed: Arc<AtomicBool>,
 }
 
 impl CallHandle {
@@ -643,8 +1401,10 @@ impl CallHandle {
         let ok = self.queue_tx.unbounded_send(request).is_ok();
 
         // Sending an error will complete the RPC. Disconnect our queue end to
-        // prevent more errors/cancel-requests to be sent.
+        // prevent more errors/cancel-requests to be sent. Also mark the call
+        // as completed to prevent any client stream items from being sent.
         self.queue_tx.disconnect();
+        self.completed.store(true, Ordering::Release);
 
         ok
     }
@@ -674,11 +1434,11 @@ impl CallHandle {
                 Some(CallUpdate::Complete { .. }) => {
                     return Ok(())
                 },
-                Some(CallUpdate::Error { status }) if status == Status::Cancelled => {
+                Some(CallUpdate::Error { error }) if error.code() == Status::Cancelled => {
                     return Ok(())
                 },
-                Some(CallUpdate::Error { status }) => {
-                    return Err(Error::from(status))
+                Some(CallUpdate::Error { error }) => {
+                    return Err(error)
                 },
                 None => {
                     return Ok(())
@@ -699,12 +1459,104 @@ impl Drop for CallHandle {
 }
 
 
+/// Request for starting or opening a call.
+///
+/// Calls are identified by channel, service, method, and call ID, so
+/// concurrent calls to the same method need distinct call IDs. Prefer
+/// creating requests via [`ClientHandle::request`], which assigns them
+/// automatically.
+#[derive(Debug, Clone)]
 pub struct Request<M> {
     pub channel_id: u32,
-    pub service_id: u32,
-    pub method_id: u32,
-    pub call_id: u32,
+    pub service_id: ServiceId,
+    pub method_id: MethodId,
+    pub call_id: CallId,
     pub message: M,
+
+    /// Maximum time to wait for the call to complete. If exceeded, the call is
+    /// cancelled and completes with status `DeadlineExceeded`, with details
+    /// available via [`Error::timeout`].
+    pub deadline: Option<Duration>,
+}
+
+
+/// Builder for [`Request`]s, created via [`ClientHandle::request`].
+///
+/// The channel, service, and method IDs are required. The call ID is taken
+/// from the client unless set explicitly, and there is no deadline by default.
+#[derive(Debug, Clone)]
+pub struct RequestBuilder<M> {
+    call_ids: Arc<CallIds>,
+    channel_id: Option<u32>,
+    service_id: Option<ServiceId>,
+    method_id: Option<MethodId>,
+    call_id: Option<CallId>,
+    message: M,
+    deadline: Option<Duration>,
+}
+
+impl<M> RequestBuilder<M> {
+    fn new(call_ids: Arc<CallIds>, message: M) -> Self {
+        Self {
+            call_ids,
+            channel_id: None,
+            service_id: None,
+            method_id: None,
+            call_id: None,
+            message,
+            deadline: None,
+        }
+    }
+
+    pub fn channel_id(mut self, id: u32) -> Self {
+        self.channel_id = Some(id);
+        self
+    }
+
+    pub fn service_id(mut self, id: impl Into<ServiceId>) -> Self {
+        self.service_id = Some(id.into());
+        self
+    }
+
+    pub fn method_id(mut self, id: impl Into<MethodId>) -> Self {
+        self.method_id = Some(id.into());
+        self
+    }
+
+    /// Set service and method ID from the given path, e.g.
+    /// `maestro_pw.Maestro/GetSoftwareInfo`.
+    pub fn path(self, path: impl Into<Path>) -> Self {
+        let path = path.into();
+
+        self.service_id(path.service().hash())
+            .method_id(path.method().hash())
+    }
+
+    /// Use the given call ID instead of assigning a new one.
+    pub fn call_id(mut self, id: impl Into<CallId>) -> Self {
+        self.call_id = Some(id.into());
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn build(self) -> Result<Request<M>, Error> {
+        fn required<T>(value: Option<T>, name: &str) -> Result<T, Error> {
+            value.ok_or_else(|| Error::invalid_argument(format!("request {name} not set")))
+        }
+
+        Ok(Request {
+            channel_id: required(self.channel_id, "channel ID")?,
+            service_id: required(self.service_id, "service ID")?,
+            method_id: required(self.method_id, "method ID")?,
+            call_id: self.call_id.unwrap_or_else(|| CallId(self.call_ids.next())),
+            message: self.message,
+            deadline: self.deadline,
+        })
+    }
 }
 
 
@@ -724,15 +1576,15 @@ where
         };
 
         let data = match update {
-            CallUpdate::Complete { data, status: Status::Ok } => data,
-            CallUpdate::Complete { status, .. } => return Err(Error::from(status)),
-            CallUpdate::Error { status } => return Err(Error::from(status)),
+            CallUpdate::Complete { data, status: Status::Ok, .. } => data,
+            CallUpdate::Complete { status, remote, .. } => return Err(status_error(status, remote)),
+            CallUpdate::Error { error } => return Err(error),
             CallUpdate::StreamItem { .. } => unreachable!("received stream update on unary rpc"),
         };
 
         self.handle.queue_tx.disconnect();
 
-        let message = M::decode(&data[..])?;
+        let message = M::decode(data)?;
         Ok(message)
     }
 
@@ -755,9 +1607,18 @@ where
     pub fn is_complete(&self) -> bool {
         self.handle.is_complete()
     }
+
+    pub fn uid(&self) -> CallUid {
+        self.handle.uid
+    }
 }
 
 
+/// One of the consumers of a [`StreamResponse`] created via
+/// [`StreamResponse::split`].
+pub type SharedStream<M> = BoxStream<'static, Result<M, Error>>;
+
+
 pub struct StreamResponse<M> {
     marker: std::marker::PhantomData<M>,
     handle: CallHandle,
@@ -774,6 +1635,145 @@ where
         }
     }
 
+    /// Convert this response into a stream owning the call.
+    ///
+    /// Dropping the stream drops the call, cancelling it unless configured
+    /// otherwise via [`cancel_on_drop`](Self::cancel_on_drop).
+    pub fn into_stream(self) -> impl Stream<Item = Result<M, Error>> {
+        futures::stream::unfold(self, |mut call| async move {
+            let item = call.stream().next().await?;
+            Some((item, call))
+        })
+    }
+
+    /// Collect all items of the stream until it completes.
+    ///
+    /// Fails with the first error received. Only useful for finite streams,
+    /// as this will not return otherwise.
+    pub async fn try_collect<C>(mut self) -> Result<C, Error>
+    where
+        C: FromIterator<M>,
+    {
+        let mut items = Vec::new();
+
+        let mut stream = self.stream();
+        while let Some(item) = stream.next().await {
+            items.push(item?);
+        }
+
+        Ok(items.into_iter().collect())
+    }
+
+    /// Stream items as long as they satisfy the given predicate.
+    ///
+    /// The stream ends before the first item for which `pred` returns
+    /// `false`. Errors are always passed through.
+    pub fn take_while<'a, F>(&'a mut self, pred: F) -> impl Stream<Item = Result<M, Error>> + 'a
+    where
+        F: Fn(&M) -> bool + 'a,
+    {
+        self.stream().take_while(move |item| {
+            let keep = match item {
+                Ok(message) => pred(message),
+                Err(_) => true,
+            };
+
+            futures::future::ready(keep)
+        })
+    }
+
+    /// Split the stream into two consumers that both receive every item.
+    ///
+    /// Items are forwarded by a separate task, buffering up to `capacity`
+    /// items per consumer. If a consumer falls behind by more than that, the
+    /// oldest items are dropped for it and a warning is logged. The call is
+    /// cancelled once both consumers have been dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime or if `capacity` is zero.
+    pub fn split(self, capacity: usize) -> (SharedStream<M>, SharedStream<M>)
+    where
+        M: Clone + Send + 'static,
+    {
+        let (tx, rx1) = tokio::sync::broadcast::channel(capacity);
+        let rx2 = tx.subscribe();
+
+        tokio::spawn(async move {
+            let mut stream = std::pin::pin!(self.into_stream());
+
+            loop {
+                let item = tokio::select! {
+                    item = stream.next() => item,
+                    _ = tx.closed() => break,
+                };
+
+                // ends the consumers' streams once the sender has been dropped
+                let Some(item) = item else { break };
+
+                if tx.send(item).is_err() {
+                    break;
+                }
+            }
+        });
+
+        (broadcast_stream(rx1), broadcast_stream(rx2))
+    }
+
+    pub fn abandon(&mut self) -> bool {
+        self.handle.abandon()
+    }
+
+    pub fn cancel_on_drop(&mut self, cacnel: bool) {
+        self.handle.cancel_on_drop(cacnel)
+    }
+
+    pub fn cancel(&mut self) -> bool {
+        self.handle.cancel()
+    }
+
+    pub async fn cancel_and_wait(&mut self) -> Result<(), Error> {
+        self.handle.cancel_and_wait().await
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.handle.is_complete()
+    }
+
+    pub fn uid(&self) -> CallUid {
+        self.handle.uid
+    }
+}
+
+
+pub struct BidiStreamResponse<M1, M2> {
+    marker1: std::marker::PhantomData<M1>,
+    marker2: std::marker::PhantomData<M2>,
+    handle: CallHandle,
+}
+
+impl<M1, M2> BidiStreamResponse<M1, M2>
+where
+    M1: Message,
+    M2: Message + Default,
+{
+    pub fn stream(&mut self) -> ServerStream<'_, M2> {
+        ServerStream {
+            marker: std::marker::PhantomData,
+            handle: &mut self.handle,
+        }
+    }
+
+    pub fn sink(&self) -> ClientStream<M1> {
+        ClientStream {
+            marker: std::marker::PhantomData,
+            uid: self.handle.uid,
+            queue_tx: self.handle.queue_tx.clone(),
+            completed: self.handle.completed.clone(),
+            closed: false,
+        }
+    }
+
     pub fn abandon(&mut self) -> bool {
         self.handle.abandon()
     }
@@ -793,8 +1793,81 @@ where
     pub fn is_complete(&self) -> bool {
         self.handle.is_complete()
     }
+
+    pub fn uid(&self) -> CallUid {
+        self.handle.uid
+    }
+}
+
+
+/// Sending half of a client stream.
+///
+/// Closing the sink signals the end of the client stream to the server.
+pub struct ClientStream<M> {
+    marker: std::marker::PhantomData<M>,
+    uid: CallUid,
+    queue_tx: mpsc::UnboundedSender<CallRequest>,
+    completed: Arc<AtomicBool>,
+    closed: bool,
+}
+
+impl<M> ClientStream<M> {
+    fn is_completed(&self) -> bool {
+        self.completed.load(Ordering::Acquire) || self.queue_tx.is_closed()
+    }
+
+    fn check_open(&self) -> Result<(), Error> {
+        if self.closed {
+            Err(Error::aborted("the client stream has been closed"))
+        } else if self.is_completed() {
+            Err(Error::aborted("the call has been completed"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<M> Sink<M> for ClientStream<M>
+where
+    M: Message,
+{
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(self.check_open())
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: M) -> Result<(), Self::Error> {
+        self.check_open()?;
+
+        let request = CallRequest::StreamItem { uid: self.uid, payload: item.encode_to_vec() };
+        self.queue_tx.unbounded_send(request)
+            .map_err(|_| Error::aborted("the client stream has been closed"))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, _cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if !self.closed {
+            self.closed = true;
+
+            // There is no point in ending the client stream of a completed
+            // call. Ignore send errors, as the client has been shut down in
+            // that case.
+            if !self.is_completed() {
+                let request = CallRequest::StreamEnd { uid: self.uid };
+                let _ = self.queue_tx.unbounded_send(request);
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
 }
 
+impl<M> Unpin for ClientStream<M> {}
+
 
 pub struct ServerStream<'a, M> {
     marker: std::marker::PhantomData<&'a mut M>,
@@ -825,14 +1898,14 @@ where
                 self.handle.queue_tx.disconnect();
                 return Poll::Ready(None);
             },
-            CallUpdate::Error { status } => {
+            CallUpdate::Error { error } => {
                 self.handle.receiver.close();
                 self.handle.queue_tx.disconnect();
-                return Poll::Ready(Some(Err(Error::from(status))));
+                return Poll::Ready(Some(Err(error)));
             },
         };
 
-        let result = match M::decode(&data[..]) {
+        let result = match M::decode(data) {
             Ok(message) => {
                 Ok(message)
             },
@@ -860,11 +1933,24 @@ where
 }
 
 
+/// Definition of a statically known unary RPC method.
+///
+/// IDs can be computed at compile time via [`IdRef::hash`](super::id::IdRef::hash).
+pub trait RpcMethod {
+    const SERVICE_ID: u32;
+    const METHOD_ID: u32;
+
+    type Request: Message;
+    type Response: Message + Default;
+}
+
+
 #[derive(Debug, Clone)]
 pub struct UnaryRpc<M1, M2> {
     marker1: std::marker::PhantomData<*const M1>,
     marker2: std::marker::PhantomData<*const M2>,
-    path: Path,
+    service_id: ServiceId,
+    method_id: MethodId,
 }
 
 impl<M1, M2> UnaryRpc<M1, M2>
@@ -873,36 +1959,41 @@ where
     M2: Message + Default,
 {
     pub fn new(path: impl Into<Path>) -> Self {
+        let path = path.into();
+
         Self {
             marker1: std::marker::PhantomData,
             marker2: std::marker::PhantomData,
-            path: path.into(),
+            service_id: ServiceId(path.service().hash()),
+            method_id: MethodId(path.method().hash()),
         }
     }
 
-    pub fn call(&self, handle: &mut ClientHandle, channel_id: u32, call_id: u32, message: M1)
+    pub fn call(&self, handle: &mut ClientHandle, channel_id: u32, call_id: impl Into<CallId>, message: M1)
         -> Result<UnaryResponse<M2>, Error>
     {
         let req = Request {
             channel_id,
-            service_id: self.path.service().hash(),
-            method_id: self.path.method().hash(),
-            call_id,
+            service_id: self.service_id,
+            method_id: self.method_id,
+            call_id: call_id.into(),
             message,
+            deadline: None,
         };
 
         handle.call_unary(req)
     }
 
-    pub fn open(&self, handle: &mut ClientHandle, channel_id: u32, call_id: u32)
+    pub fn open(&self, handle: &mut ClientHandle, channel_id: u32, call_id: impl Into<CallId>)
         -> Result<UnaryResponse<M2>, Error>
     {
         let req = Request {
             channel_id,
-            service_id: self.path.service().hash(),
-            method_id: self.path.method().hash(),
-            call_id,
+            service_id: self.service_id,
+            method_id: self.method_id,
+            call_id: call_id.into(),
             message: (),
+            deadline: None,
         };
 
         handle.open_unary(req)
@@ -914,7 +2005,8 @@ where
 pub struct ServerStreamRpc<M1, M2> {
     marker1: std::marker::PhantomData<*const M1>,
     marker2: std::marker::PhantomData<*const M2>,
-    path: Path,
+    service_id: ServiceId,
+    method_id: MethodId,
 }
 
 impl<M1, M2> ServerStreamRpc<M1, M2>
@@ -923,38 +2015,714 @@ where
     M2: Message + Default,
 {
     pub fn new(path: impl Into<Path>) -> Self {
+        let path = path.into();
+
         Self {
             marker1: std::marker::PhantomData,
             marker2: std::marker::PhantomData,
-            path: path.into(),
+            service_id: ServiceId(path.service().hash()),
+            method_id: MethodId(path.method().hash()),
         }
     }
 
-    pub fn call(&self, handle: &mut ClientHandle, channel_id: u32, call_id: u32, message: M1)
+    pub fn call(&self, handle: &mut ClientHandle, channel_id: u32, call_id: impl Into<CallId>, message: M1)
         -> Result<StreamResponse<M2>, Error>
     {
         let req = Request {
             channel_id,
-            service_id: self.path.service().hash(),
-            method_id: self.path.method().hash(),
-            call_id,
+            service_id: self.service_id,
+            method_id: self.method_id,
+            call_id: call_id.into(),
             message,
+            deadline: None,
         };
 
         handle.call_server_stream(req)
     }
 
-    pub fn open(&self, handle: &mut ClientHandle, channel_id: u32, call_id: u32)
+    pub fn open(&self, handle: &mut ClientHandle, channel_id: u32, call_id: impl Into<CallId>)
         -> Result<StreamResponse<M2>, Error>
     {
         let req = Request {
             channel_id,
-            service_id: self.path.service().hash(),
-            method_id: self.path.method().hash(),
-            call_id,
+            service_id: self.service_id,
+            method_id: self.method_id,
+            call_id: call_id.into(),
             message: (),
+            deadline: None,
         };
 
         handle.open_server_stream(req)
     }
 }
+
+
+#[derive(Debug, Clone)]
+pub struct BidiStreamRpc<M1, M2> {
+    marker1: std::marker::PhantomData<*const M1>,
+    marker2: std::marker::PhantomData<*const M2>,
+    service_id: ServiceId,
+    method_id: MethodId,
+}
+
+impl<M1, M2> BidiStreamRpc<M1, M2>
+where
+    M1: Message,
+    M2: Message + Default,
+{
+    pub fn new(path: impl Into<Path>) -> Self {
+        let path = path.into();
+
+        Self {
+            marker1: std::marker::PhantomData,
+            marker2: std::marker::PhantomData,
+            service_id: ServiceId(path.service().hash()),
+            method_id: MethodId(path.method().hash()),
+        }
+    }
+
+    pub fn call(&self, handle: &mut ClientHandle, channel_id: u32, call_id: impl Into<CallId>, message: M1)
+        -> Result<BidiStreamResponse<M1, M2>, Error>
+    {
+        let req = Request {
+            channel_id,
+            service_id: self.service_id,
+            method_id: self.method_id,
+            call_id: call_id.into(),
+            message,
+            deadline: None,
+        };
+
+        handle.call_bidi_stream(req)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::pwrpc::testing;
+
+    fn request(call_id: u32, deadline: Option<Duration>) -> Request<()> {
+        Request {
+            channel_id: 18,
+            service_id: ServiceId(0x01),
+            method_id: MethodId(0x02),
+            call_id: CallId(call_id),
+            message: (),
+            deadline,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deadline_exceeded() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+
+        let mut call: UnaryResponse<()> = handle.call_unary(request(0, Some(Duration::from_secs(1))))
+            .unwrap();
+
+        let err = testing::run_with_client(&mut client, call.result()).await.unwrap_err();
+        assert_eq!(err.code(), Status::DeadlineExceeded);
+        assert!(!err.is_remote());
+
+        let timeout = err.timeout().unwrap();
+        assert_eq!(timeout.call_id, CallId(0));
+        assert_eq!(timeout.elapsed, Duration::from_secs(1));
+        assert_eq!(err.message(), "deadline of 1s exceeded for call_id=0x00");
+
+        let packet = device.recv().await.unwrap();
+        assert_eq!(packet.r#type, PacketType::Request as i32);
+
+        let packet = device.recv().await.unwrap();
+        assert_eq!(packet.r#type, PacketType::ClientError as i32);
+        assert_eq!(packet.status, Status::Cancelled as u32);
+
+        assert_eq!(handle.in_flight_count(), 0);
+        assert!(device.try_recv().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deadline_exceeded_open() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+
+        let mut call: UnaryResponse<()> = handle.open_unary(request(0, Some(Duration::from_secs(1))))
+            .unwrap();
+
+        let result = testing::run_with_client(&mut client, call.result()).await;
+        assert_eq!(result.unwrap_err().code(), Status::DeadlineExceeded);
+
+        // the call has never been sent, so there is nothing to cancel
+        assert!(device.try_recv().is_none());
+        assert_eq!(handle.in_flight_count(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deadline_not_exceeded() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+
+        let mut call: UnaryResponse<()> = handle.call_unary(request(0, Some(Duration::from_secs(1))))
+            .unwrap();
+
+        let exchange = async {
+            let request = device.recv().await.unwrap();
+            device.send(testing::response(&request, &())).unwrap();
+
+            call.result().await
+        };
+
+        let result = testing::run_with_client(&mut client, exchange).await;
+        assert!(result.is_ok());
+
+        // advance past the deadline, no cancellation must be sent
+        let _ = tokio::time::timeout(Duration::from_secs(2), client.run()).await;
+
+        assert!(device.try_recv().is_none());
+        assert_eq!(handle.in_flight_count(), 0);
+    }
+
+    #[test]
+    fn test_request_builder() {
+        let (stream, _device) = testing::pair();
+
+        let client = Client::new(stream);
+        let handle = client.handle();
+
+        let req = handle.request(())
+            .channel_id(18)
+            .path("maestro_pw.Maestro/GetSoftwareInfo")
+            .build()
+            .unwrap();
+
+        assert_eq!(req.service_id, ServiceId(0x7ede71ea));
+        assert_eq!(req.method_id, MethodId(0x7199fa44));
+        assert_eq!(req.call_id, CallId(1));
+        assert_eq!(req.deadline, None);
+
+        // call IDs are shared between handles
+        let other = handle.clone();
+        assert_eq!(other.next_call_id(), CallId(2));
+
+        let req = other.request(())
+            .channel_id(18)
+            .service_id(1)
+            .method_id(2)
+            .deadline(Duration::from_secs(1))
+            .build()
+            .unwrap();
+
+        assert_eq!(req.call_id, CallId(3));
+        assert_eq!(req.deadline, Some(Duration::from_secs(1)));
+
+        // explicit call IDs do not consume one
+        let req = handle.request(()).channel_id(18).service_id(1).method_id(2).call_id(0).build().unwrap();
+        assert_eq!(req.call_id, CallId(0));
+        assert_eq!(handle.next_call_id(), CallId(4));
+
+        let err = handle.request(()).service_id(1).method_id(2).build().unwrap_err();
+        assert_eq!(err.code(), Status::InvalidArgument);
+
+        // zero is skipped when wrapping around
+        let ids = CallIds { next: AtomicU32::new(u32::MAX) };
+        assert_eq!(ids.next(), u32::MAX);
+        assert_eq!(ids.next(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+        let metrics = client.metrics();
+
+        let mut call: UnaryResponse<()> = handle.call_unary(request(0, None)).unwrap();
+
+        let exchange = async {
+            let request = device.recv().await.unwrap();
+            let response = testing::response(&request, &());
+            device.send(response.clone()).unwrap();
+
+            call.result().await.unwrap();
+            (request, response)
+        };
+
+        let (request, response) = testing::run_with_client(&mut client, exchange).await;
+
+        assert_eq!(metrics.packets_sent.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.packets_received.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.bytes_sent.load(Ordering::Relaxed), request.encoded_len() as u64);
+        assert_eq!(metrics.bytes_received.load(Ordering::Relaxed), response.encoded_len() as u64);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_unary() {
+        /// Receive the next request, skipping cancellations of timed-out calls.
+        async fn recv_request(device: &mut testing::MockDevice) -> RpcPacket {
+            loop {
+                let packet = device.recv().await.unwrap();
+                if packet.r#type == PacketType::Request as i32 {
+                    return packet;
+                }
+            }
+        }
+
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+
+        let policy = RetryPolicy::default();
+
+        let factory = |h: &ClientHandle| {
+            h.request(()).channel_id(18).service_id(0x01).method_id(0x02)
+                .deadline(Duration::from_millis(50))
+                .build()
+        };
+
+        let exchange = async {
+            // time out once, then respond
+            let device = async {
+                let first = recv_request(&mut device).await;
+                let second = recv_request(&mut device).await;
+                device.send(testing::response(&second, &42u32)).unwrap();
+
+                let third = recv_request(&mut device).await;
+                device.send(testing::server_error(&third, Status::NotFound)).unwrap();
+
+                let fourth = recv_request(&mut device).await;
+                device.send(testing::server_error(&fourth, Status::NotFound)).unwrap();
+
+                let fifth = recv_request(&mut device).await;
+                device.send(testing::server_error(&fifth, Status::PermissionDenied)).unwrap();
+
+                [first, second, third, fourth, fifth].map(|p| p.call_id)
+            };
+
+            let calls = async {
+                let a = handle.retry_unary::<(), u32, _>(factory, policy).await;
+
+                // remote errors are not retried
+                let b = handle.retry_unary::<(), u32, _>(factory, policy).await;
+
+                // unless requested
+                let policy = RetryPolicy { max_attempts: 2, retry_on: |_| true, ..policy };
+                let c = handle.retry_unary::<(), u32, _>(factory, policy).await;
+
+                (a, b, c)
+            };
+
+            futures::join!(calls, device)
+        };
+
+        let ((a, b, c), ids) = testing::run_with_client(&mut client, exchange).await;
+
+        assert_eq!(a.unwrap(), 42);
+        assert_eq!(b.unwrap_err().code(), Status::NotFound);
+        assert_eq!(c.unwrap_err().code(), Status::PermissionDenied);
+
+        // each attempt uses a new call ID
+        assert_eq!(ids, [1, 2, 3, 4, 5]);
+
+        assert!(RetryPolicy::is_transport_error(&std::io::Error::from(std::io::ErrorKind::BrokenPipe).into()));
+        assert!(RetryPolicy::is_transport_error(&Error::unavailable("closed")));
+        assert!(!RetryPolicy::is_transport_error(&Error::remote(Status::Unavailable)));
+        assert!(!RetryPolicy::is_transport_error(&Error::invalid_argument("invalid")));
+    }
+
+    #[tokio::test]
+    async fn test_sink() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+        let metrics = client.metrics();
+
+        let mut call: UnaryResponse<()> = handle.call_unary(request(1, None)).unwrap();
+
+        let request = testing::run_with_client(&mut client, device.recv()).await.unwrap();
+
+        // packet for the pending call
+        let cancel = RpcPacket {
+            r#type: PacketType::ClientError.into(),
+            status: Status::Cancelled.into(),
+            ..request.clone()
+        };
+        client.send(cancel.clone()).await.unwrap();
+        assert_eq!(device.recv().await.unwrap(), cancel);
+
+        // packet not belonging to any call is sent as well
+        let other = RpcPacket { call_id: 2, ..request.clone() };
+        client.send(other.clone()).await.unwrap();
+        assert_eq!(device.recv().await.unwrap(), other);
+
+        assert_eq!(metrics.packets_sent.load(Ordering::Relaxed), 3);
+
+        // the call is not affected by injected packets
+        assert!(!call.is_complete());
+
+        device.send(testing::response(&request, &())).unwrap();
+        testing::run_with_client(&mut client, call.result()).await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_ready() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let handle = client.handle();
+
+        let exchange = async {
+            let err = handle.wait_ready(Duration::from_secs(1)).await.unwrap_err();
+            assert_eq!(err.code(), Status::DeadlineExceeded);
+
+            // unsolicited packet, not matching any call
+            let packet = RpcPacket {
+                r#type: PacketType::Response.into(),
+                channel_id: 18,
+                call_id: 7,
+                ..Default::default()
+            };
+            device.send(packet).unwrap();
+
+            handle.wait_ready(Duration::from_secs(1)).await.unwrap();
+            handle.clone().wait_ready(Duration::ZERO).await.unwrap();
+        };
+
+        testing::run_with_client(&mut client, exchange).await;
+
+        drop(client);
+
+        let (stream, _device) = testing::pair();
+        let handle = Client::new(stream).handle();
+
+        let err = handle.wait_ready(Duration::from_secs(1)).await.unwrap_err();
+        assert_eq!(err.code(), Status::Aborted);
+    }
+
+    #[tokio::test]
+    async fn test_server_error() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+
+        let mut call: UnaryResponse<()> = handle.call_unary(request(0, None)).unwrap();
+        let mut stream: StreamResponse<u32> = handle.call_server_stream(request(1, None)).unwrap();
+
+        let exchange = async {
+            let request = device.recv().await.unwrap();
+            device.send(testing::server_error(&request, Status::Unimplemented)).unwrap();
+
+            let request = device.recv().await.unwrap();
+            device.send(testing::server_error(&request, Status::NotFound)).unwrap();
+
+            (call.result().await, stream.stream().next().await)
+        };
+
+        let (result, item) = testing::run_with_client(&mut client, exchange).await;
+
+        let err = result.unwrap_err();
+        assert_eq!(err.code(), Status::Unimplemented);
+        assert!(err.is_remote());
+
+        let err = item.unwrap().unwrap_err();
+        assert_eq!(err.code(), Status::NotFound);
+        assert!(err.is_remote());
+    }
+
+    #[tokio::test]
+    async fn test_split() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+
+        let call: StreamResponse<u32> = handle.call_server_stream(request(0, None)).unwrap();
+        let (a, b) = call.split(4);
+
+        let exchange = async {
+            let request = device.recv().await.unwrap();
+            device.send(testing::server_stream(&request, &1u32)).unwrap();
+            device.send(testing::server_stream(&request, &2u32)).unwrap();
+            device.send(testing::response(&request, &())).unwrap();
+
+            let a = a.map(Result::unwrap).collect::<Vec<_>>();
+            let b = b.map(Result::unwrap).collect::<Vec<_>>();
+            futures::join!(a, b)
+        };
+
+        let (a, b) = testing::run_with_client(&mut client, exchange).await;
+        assert_eq!(a, [1, 2]);
+        assert_eq!(b, [1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_split_dropped() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+
+        let call: StreamResponse<u32> = handle.call_server_stream(request(0, None)).unwrap();
+        let (a, b) = call.split(4);
+
+        let exchange = async {
+            let packet = device.recv().await.unwrap();
+            assert_eq!(packet.r#type, PacketType::Request as i32);
+
+            drop(a);
+            drop(b);
+
+            let packet = device.recv().await.unwrap();
+            assert_eq!(packet.r#type, PacketType::ClientError as i32);
+            assert_eq!(packet.status, Status::Cancelled as u32);
+        };
+
+        testing::run_with_client(&mut client, exchange).await;
+        assert_eq!(handle.in_flight_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+
+        let mut call: StreamResponse<u32> = handle.call_server_stream(request(1, None)).unwrap();
+
+        let exchange = async {
+            let packet = device.recv().await.unwrap();
+            assert_eq!(packet.r#type, PacketType::Request as i32);
+        };
+
+        testing::run_with_client(&mut client, exchange).await;
+
+        client.graceful_shutdown(Duration::from_secs(1)).await.unwrap();
+
+        let packet = device.recv().await.unwrap();
+        assert_eq!(packet.r#type, PacketType::ClientError as i32);
+        assert_eq!(packet.status, Status::Cancelled as u32);
+        assert_eq!(packet.call_id, 1);
+
+        // the transport has been closed
+        assert!(device.recv().await.is_none());
+
+        let err = call.stream().next().await.unwrap().unwrap_err();
+        assert_eq!(err.code(), Status::Aborted);
+        assert_eq!(handle.in_flight_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_handles_dropped() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+        let other = handle.clone();
+        assert_eq!(client.handle_count(), 2);
+
+        let mut call: UnaryResponse<()> = handle.call_unary(request(0, None)).unwrap();
+
+        drop(handle);
+        drop(other);
+        assert_eq!(client.handle_count(), 0);
+
+        // the client keeps running until the pending call has completed
+        let exchange = async {
+            let request = device.recv().await.unwrap();
+            device.send(testing::response(&request, &())).unwrap();
+
+            call.result().await
+        };
+
+        let (res, result) = tokio::join!(client.run(), exchange);
+        res.unwrap();
+        assert!(result.is_ok());
+
+        // the transport has been closed
+        assert!(device.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_max_in_flight() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::with_capacity(stream, 2);
+        let mut handle = client.handle();
+
+        let mut call1: UnaryResponse<()> = handle.call_unary(request(1, None)).unwrap();
+        let mut call2: UnaryResponse<()> = handle.call_unary(request(2, None)).unwrap();
+        assert_eq!(handle.in_flight_count(), 2);
+
+        let err = handle.call_unary::<(), ()>(request(3, None)).err().unwrap();
+        assert_eq!(err.code(), Status::ResourceExhausted);
+
+        // completed calls release their slot
+        let exchange = async {
+            let request = device.recv().await.unwrap();
+            assert_eq!(request.call_id, 1);
+
+            device.send(testing::response(&request, &())).unwrap();
+            call1.result().await
+        };
+
+        testing::run_with_client(&mut client, exchange).await.unwrap();
+        assert_eq!(handle.in_flight_count(), 1);
+
+        // cancelled calls release their slot
+        testing::run_with_client(&mut client, call2.cancel_and_wait()).await.unwrap();
+        assert_eq!(handle.in_flight_count(), 0);
+
+        // calls aborted on termination release their slot
+        let _call3: UnaryResponse<()> = handle.open_unary(request(3, None)).unwrap();
+        let _call4: UnaryResponse<()> = handle.call_unary(request(4, None)).unwrap();
+
+        let exchange = async {
+            while device.recv().await.unwrap().call_id != 4 {}
+        };
+
+        testing::run_with_client(&mut client, exchange).await;
+        assert_eq!(handle.in_flight_count(), 2);
+
+        client.terminate().await.unwrap();
+        assert_eq!(handle.in_flight_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_bidi_stream() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+
+        let req = Request {
+            channel_id: 18,
+            service_id: ServiceId(0x01),
+            method_id: MethodId(0x02),
+            call_id: CallId(0),
+            message: 1u32,
+            deadline: None,
+        };
+
+        let mut call: BidiStreamResponse<u32, u32> = handle.call_bidi_stream(req).unwrap();
+        let mut sink = call.sink();
+
+        let exchange = async {
+            let request = device.recv().await.unwrap();
+            assert_eq!(request.r#type, PacketType::Request as i32);
+            assert_eq!(u32::decode(request.payload.clone()).unwrap(), 1);
+
+            sink.send(2).await.unwrap();
+            sink.send(3).await.unwrap();
+            sink.close().await.unwrap();
+
+            for expected in [2, 3] {
+                let packet = device.recv().await.unwrap();
+                assert_eq!(packet.r#type, PacketType::ClientStream as i32);
+                assert_eq!(u32::decode(packet.payload).unwrap(), expected);
+            }
+
+            let packet = device.recv().await.unwrap();
+            assert_eq!(packet.r#type, PacketType::ClientStreamEnd as i32);
+
+            device.send(testing::server_stream(&request, &4u32)).unwrap();
+            device.send(testing::server_stream(&request, &5u32)).unwrap();
+            device.send(testing::response(&request, &())).unwrap();
+
+            call.stream().map(Result::unwrap).collect::<Vec<_>>().await
+        };
+
+        let items = testing::run_with_client(&mut client, exchange).await;
+        assert_eq!(items, [4, 5]);
+
+        // the call has been completed, sending must fail
+        let mut sink = call.sink();
+        assert!(sink.send(6).await.is_err());
+        assert!(device.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_bidi_stream_cancelled() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+
+        let req = Request {
+            channel_id: 18,
+            service_id: ServiceId(0x01),
+            method_id: MethodId(0x02),
+            call_id: CallId(0),
+            message: 1u32,
+            deadline: None,
+        };
+
+        let mut call: BidiStreamResponse<u32, u32> = handle.call_bidi_stream(req).unwrap();
+        let mut sink = call.sink();
+
+        call.cancel();
+        assert!(sink.send(2).await.is_err());
+
+        let exchange = async {
+            let packet = device.recv().await.unwrap();
+            assert_eq!(packet.r#type, PacketType::Request as i32);
+
+            let packet = device.recv().await.unwrap();
+            assert_eq!(packet.r#type, PacketType::ClientError as i32);
+            assert_eq!(packet.status, Status::Cancelled as u32);
+        };
+
+        testing::run_with_client(&mut client, exchange).await;
+        assert!(device.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_by_uid() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+
+        let mut call: StreamResponse<u32> = handle.call_server_stream(request(0, None)).unwrap();
+        let uid = call.uid();
+
+        let exchange = async {
+            let request = device.recv().await.unwrap();
+            device.send(testing::server_stream(&request, &1u32)).unwrap();
+
+            let mut stream = call.stream();
+            assert_eq!(stream.next().await.unwrap().unwrap(), 1);
+
+            handle.cancel(uid).unwrap();
+            assert!(stream.next().await.is_none());
+
+            let packet = device.recv().await.unwrap();
+            assert_eq!(packet.r#type, PacketType::ClientError as i32);
+            assert_eq!(packet.status, Status::Cancelled as u32);
+            assert_eq!(packet.call_id, uid.call_id());
+        };
+
+        testing::run_with_client(&mut client, exchange).await;
+
+        assert!(call.is_complete());
+        assert!(device.try_recv().is_none());
+    }
+
+    #[test]
+    fn test_handle_bounds() {
+        fn assert_impl<T: Clone + Send + Sync + 'static>() {}
+
+        assert_impl::<ClientHandle>();
+    }
+}
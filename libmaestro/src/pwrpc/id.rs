@@ -1,7 +1,29 @@
+//! Service and method identifiers.
+//!
+//! Services and methods are not referred to by name but by a 32 bit hash of
+//! their name. This is the same hash as used by pw_rpc (the "65599 hash"),
+//! computed over the Unicode code points `c[0..n]` of the name as
+//!
+//! ```text
+//! hash = len + c[0] * 65599^1 + c[1] * 65599^2 + ... + c[n-1] * 65599^n
+//! ```
+//!
+//! with wrapping 32 bit arithmetic, where `len` is the length of the name in
+//! bytes. Services are identified by their fully qualified name (e.g.
+//! `maestro_pw.Maestro`), and methods by their plain name (e.g.
+//! `GetSoftwareInfo`).
+
+use std::collections::HashMap;
+
 pub type Hash = u32;
 
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Service or method identifier.
+///
+/// On the wire, identifiers are only represented by their hash. Equality and
+/// hashing are therefore based on the hash value, i.e., two names with
+/// colliding hashes are considered equal.
+#[derive(Debug, Clone)]
 pub struct Id {
     name: String,
 }
@@ -22,6 +44,37 @@ impl Id {
     pub fn as_ref(&self) -> IdRef<'_> {
         IdRef { name: &self.name }
     }
+
+    /// Find two distinct names with the same hash.
+    ///
+    /// Returns the first colliding pair found, or `None` if all hashes are
+    /// unique. Duplicate names are not considered a collision.
+    pub fn check_collision<'a>(names: &[&'a str]) -> Option<(&'a str, &'a str)> {
+        let mut seen: HashMap<Hash, &'a str> = HashMap::new();
+
+        for &name in names {
+            match seen.insert(hash::hash_65599(name), name) {
+                Some(other) if other != name => return Some((other, name)),
+                _ => {},
+            }
+        }
+
+        None
+    }
+}
+
+impl PartialEq for Id {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_ref() == other.as_ref()
+    }
+}
+
+impl Eq for Id {}
+
+impl std::hash::Hash for Id {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::hash::Hash::hash(&self.as_ref(), state)
+    }
 }
 
 impl<S> From<S> for Id
@@ -40,25 +93,42 @@ impl<'a> From<IdRef<'a>> for Id {
 }
 
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Borrowed service or method identifier, compared by hash like [`Id`].
+#[derive(Debug, Clone, Copy)]
 pub struct IdRef<'a> {
     name: &'a str,
 }
 
 impl<'a> IdRef<'a> {
-    pub fn new(name: &'a str) -> Self {
+    pub const fn new(name: &'a str) -> Self {
         Self { name }
     }
 
-    pub fn name(&self) -> &'a str {
+    pub const fn name(&self) -> &'a str {
         self.name
     }
 
-    pub fn hash(&self) -> Hash {
+    /// Compute the hash of this identifier. Usable in const contexts, e.g.,
+    /// `const ID: Hash = IdRef::new("maestro_pw.Maestro").hash();`.
+    pub const fn hash(&self) -> Hash {
         hash::hash_65599(self.name)
     }
 }
 
+impl PartialEq for IdRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash() == other.hash()
+    }
+}
+
+impl Eq for IdRef<'_> {}
+
+impl std::hash::Hash for IdRef<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write_u32(IdRef::hash(self))
+    }
+}
+
 impl<'a> From<&'a str> for IdRef<'a> {
     fn from(name: &'a str) -> Self {
         IdRef::new(name)
@@ -129,6 +199,10 @@ impl<'a> PathRef<'a> {
         PathRef { path, split }
     }
 
+    pub fn as_str(&self) -> &'a str {
+        self.path
+    }
+
     pub fn service(&self) -> IdRef<'a> {
         IdRef::new(&self.path[..self.split])
     }
@@ -149,20 +223,109 @@ impl<'a> From<&'a str> for PathRef<'a> {
 }
 
 
+/// Registry of known methods, mapping service and method IDs back to names.
+///
+/// Packets only carry the hashes of service and method names. This allows
+/// turning them into something human-readable, e.g. for log messages.
+#[derive(Debug, Clone, Default)]
+pub struct MethodRegistry {
+    methods: HashMap<(Hash, Hash), Path>,
+}
+
+impl MethodRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the method with the given path, e.g.
+    /// `maestro_pw.Maestro/GetSoftwareInfo`.
+    pub fn register(&mut self, path: impl Into<Path>) {
+        let path = path.into();
+        let key = (path.service().hash(), path.method().hash());
+
+        self.methods.insert(key, path);
+    }
+
+    /// Look up the path of the method with the given IDs.
+    pub fn lookup(&self, service_id: Hash, method_id: Hash) -> Option<PathRef<'_>> {
+        self.methods.get(&(service_id, method_id)).map(Path::as_ref)
+    }
+
+    /// Describe the method with the given IDs, using its path if known and
+    /// falling back to the raw IDs otherwise.
+    pub fn describe(&self, service_id: Hash, method_id: Hash) -> String {
+        match self.lookup(service_id, method_id) {
+            Some(path) => path.as_str().to_owned(),
+            None => format!("0x{service_id:08x}/0x{method_id:08x}"),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.methods.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.methods.is_empty()
+    }
+}
+
+impl<P> FromIterator<P> for MethodRegistry
+where
+    P: Into<Path>,
+{
+    fn from_iter<T: IntoIterator<Item = P>>(iter: T) -> Self {
+        let mut registry = MethodRegistry::new();
+        for path in iter {
+            registry.register(path);
+        }
+        registry
+    }
+}
+
+
 mod hash {
     const HASH_CONST: u32 = 65599;
 
-    pub fn hash_65599(id: &str) -> u32 {
+    pub const fn hash_65599(id: &str) -> u32 {
+        let bytes = id.as_bytes();
+
         let mut hash = id.len() as u32;
         let mut coef = HASH_CONST;
 
-        for chr in id.chars() {
-            hash = hash.wrapping_add(coef.wrapping_mul(chr as u32));
+        let mut i = 0;
+        while i < bytes.len() {
+            let (chr, len) = decode_utf8(bytes, i);
+
+            hash = hash.wrapping_add(coef.wrapping_mul(chr));
             coef = coef.wrapping_mul(HASH_CONST);
+            i += len;
         }
 
         hash
     }
+
+    /// Decode the UTF-8 character starting at `bytes[i]`, returning its code
+    /// point and encoded length. `str::chars()` is not usable in const
+    /// contexts, so we do this by hand. Input is expected to be valid UTF-8.
+    const fn decode_utf8(bytes: &[u8], i: usize) -> (u32, usize) {
+        let b0 = bytes[i] as u32;
+
+        if b0 < 0x80 {
+            (b0, 1)
+        } else if b0 < 0xe0 {
+            let b1 = bytes[i + 1] as u32 & 0x3f;
+            (((b0 & 0x1f) << 6) | b1, 2)
+        } else if b0 < 0xf0 {
+            let b1 = bytes[i + 1] as u32 & 0x3f;
+            let b2 = bytes[i + 2] as u32 & 0x3f;
+            (((b0 & 0x0f) << 12) | (b1 << 6) | b2, 3)
+        } else {
+            let b1 = bytes[i + 1] as u32 & 0x3f;
+            let b2 = bytes[i + 2] as u32 & 0x3f;
+            let b3 = bytes[i + 3] as u32 & 0x3f;
+            (((b0 & 0x07) << 18) | (b1 << 12) | (b2 << 6) | b3, 4)
+        }
+    }
 }
 
 
@@ -177,6 +340,92 @@ mod test {
         assert_eq!(IdRef::new("SubscribeToSettingsChanges").hash(), 0x2821adf5);
     }
 
+    #[test]
+    fn test_const_hash() {
+        const SERVICE_ID: Hash = IdRef::new("maestro_pw.Maestro").hash();
+        assert_eq!(SERVICE_ID, 0x7ede71ea);
+
+        // must match hashing over unicode code points
+        let id = "sërvïce/€/🎧";
+        let mut expected = id.len() as u32;
+        let mut coef = 65599u32;
+        for chr in id.chars() {
+            expected = expected.wrapping_add(coef.wrapping_mul(chr as u32));
+            coef = coef.wrapping_mul(65599);
+        }
+        assert_eq!(IdRef::new(id).hash(), expected);
+    }
+
+    #[test]
+    fn test_check_collision() {
+        // all known services and methods
+        let names = [
+            "maestro_pw.Maestro",
+            "maestro_pw.Multipoint",
+            "maestro_pw.EartipFitTest",
+            "GetSoftwareInfo",
+            "GetHardwareInfo",
+            "SubscribeRuntimeInfo",
+            "SetWallClock",
+            "WriteSetting",
+            "ReadSetting",
+            "SubscribeToSettingsChanges",
+            "SubscribeToOobeActions",
+            "SubscribeToQuietModeStatus",
+            "ForceMultipointSwitch",
+            "StartTest",
+            "EndTest",
+            "SubscribeToResults",
+        ];
+
+        assert_eq!(Id::check_collision(&names), None);
+
+        // duplicates are not collisions
+        assert_eq!(Id::check_collision(&["GetSoftwareInfo", "GetSoftwareInfo"]), None);
+
+        // same byte length, with c[0] increased by 65599 and c[1] decreased
+        // by one: c[0] * k + c[1] * k^2 stays the same
+        let a = "\u{10000}b";
+        let b = "\u{2003f}a";
+        assert_eq!(Id::check_collision(&["ReadSetting", a, b]), Some((a, b)));
+    }
+
+    #[test]
+    fn test_id_eq() {
+        use std::collections::HashSet;
+
+        assert_eq!(Id::new("GetSoftwareInfo"), Id::new("GetSoftwareInfo"));
+        assert_ne!(Id::new("GetSoftwareInfo"), Id::new("GetHardwareInfo"));
+
+        // identifiers are compared by hash
+        let a = Id::new("\u{10000}b");
+        let b = Id::new("\u{2003f}a");
+        assert_eq!(a, b);
+        assert_eq!(a.as_ref(), b.as_ref());
+
+        let set: HashSet<_> = [a, b, Id::new("ReadSetting")].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_method_registry() {
+        let registry: MethodRegistry = [
+            "maestro_pw.Maestro/GetSoftwareInfo",
+            "maestro_pw.Maestro/SubscribeToSettingsChanges",
+        ].into_iter().collect();
+
+        assert_eq!(registry.len(), 2);
+
+        let path = registry.lookup(0x7ede71ea, 0x7199fa44).unwrap();
+        assert_eq!(path.as_str(), "maestro_pw.Maestro/GetSoftwareInfo");
+        assert_eq!(path.method().name(), "GetSoftwareInfo");
+
+        assert!(registry.lookup(0x7ede71ea, 0x12345678).is_none());
+
+        assert_eq!(registry.describe(0x7ede71ea, 0x2821adf5), "maestro_pw.Maestro/SubscribeToSettingsChanges");
+        assert_eq!(registry.describe(0x7ede71ea, 0x12345678), "0x7ede71ea/0x12345678");
+    }
+
     #[test]
     fn test_path() {
         let pref = PathRef::new("maestro_pw.Maestro/GetSoftwareInfo");
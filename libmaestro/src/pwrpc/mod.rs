@@ -1,8 +1,17 @@
+#[cfg(feature = "serde")]
+pub mod capture;
+
+#[cfg(feature = "serde")]
+pub mod serde_fields;
+
 pub mod client;
 pub mod id;
+pub mod server;
+pub mod testing;
 pub mod types;
 pub mod utils;
 
 mod status;
 pub use status::Error;
 pub use status::Status;
+pub use status::Timeout;
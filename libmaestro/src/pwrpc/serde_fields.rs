@@ -0,0 +1,198 @@
+//! Serde helpers for fields of [`RpcPacket`](super::types::RpcPacket).
+//!
+//! Payloads are serialized as base64 strings and packet types by their
+//! protobuf name. For compatibility, both also deserialize from their raw
+//! representations, i.e. byte sequences and integers, respectively.
+
+use std::fmt;
+
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::Serializer;
+
+
+pub mod base64 {
+    use super::*;
+
+    use bytes::Bytes;
+
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    pub fn encode(data: &[u8]) -> String {
+        let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+        for chunk in data.chunks(3) {
+            let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+            let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    out.push('=');
+                }
+            }
+        }
+
+        out
+    }
+
+    pub fn decode(data: &str) -> Option<Vec<u8>> {
+        let data = data.as_bytes();
+        if !data.len().is_multiple_of(4) {
+            return None;
+        }
+
+        let mut out = Vec::with_capacity(data.len() / 4 * 3);
+
+        for (index, chunk) in data.chunks(4).enumerate() {
+            let last = index == data.len() / 4 - 1;
+            let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+
+            if padding > 2 || (padding > 0 && !last) {
+                return None;
+            }
+
+            let mut n = 0u32;
+            for &c in &chunk[..4 - padding] {
+                let value = ALPHABET.iter().position(|&a| a == c)?;
+                n = n << 6 | value as u32;
+            }
+            n <<= 6 * padding;
+
+            out.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+        }
+
+        Some(out)
+    }
+
+    pub fn serialize<S: Serializer>(data: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        struct PayloadVisitor;
+
+        impl<'de> Visitor<'de> for PayloadVisitor {
+            type Value = Bytes;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a base64 string or a byte sequence")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                decode(v).map(Bytes::from)
+                    .ok_or_else(|| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(Bytes::copy_from_slice(v))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut data = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(b) = seq.next_element()? {
+                    data.push(b);
+                }
+                Ok(data.into())
+            }
+        }
+
+        deserializer.deserialize_any(PayloadVisitor)
+    }
+}
+
+pub mod packet_type {
+    use super::*;
+
+    use crate::pwrpc::types::PacketType;
+
+    pub fn serialize<S: Serializer>(ty: &i32, serializer: S) -> Result<S::Ok, S::Error> {
+        match PacketType::from_i32(*ty) {
+            Some(ty) => serializer.serialize_str(ty.as_str_name()),
+            None => serializer.serialize_i32(*ty),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+        struct TypeVisitor;
+
+        impl<'de> Visitor<'de> for TypeVisitor {
+            type Value = i32;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a packet type name or number")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                PacketType::from_str_name(v).map(Into::into)
+                    .ok_or_else(|| E::unknown_variant(v, &[]))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                i32::try_from(v).map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                i32::try_from(v).map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &self))
+            }
+        }
+
+        deserializer.deserialize_any(TypeVisitor)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::pwrpc::types::{PacketType, RpcPacket};
+
+    #[test]
+    fn test_base64() {
+        let cases: &[(&[u8], &str)] = &[
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (&[0xff, 0xfe, 0x7e, 0x00], "//5+AA=="),
+        ];
+
+        for (data, encoded) in cases {
+            assert_eq!(base64::encode(data), *encoded);
+            assert_eq!(base64::decode(encoded).as_deref(), Some(*data));
+        }
+
+        assert_eq!(base64::decode("Zg="), None);
+        assert_eq!(base64::decode("Zg==Zg=="), None);
+        assert_eq!(base64::decode("Z!=="), None);
+    }
+
+    #[test]
+    fn test_packet_serde() {
+        let packet = RpcPacket::builder()
+            .packet_type(PacketType::ServerStream)
+            .channel_id(18)
+            .service_id(1)
+            .method_id(2)
+            .payload(vec![0x66, 0x6f])
+            .build()
+            .unwrap();
+
+        let json = serde_json::to_value(&packet).unwrap();
+        assert_eq!(json["type"], "SERVER_STREAM");
+        assert_eq!(json["payload"], "Zm8=");
+
+        let decoded: RpcPacket = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, packet);
+
+        // raw representation
+        let mut json = serde_json::to_value(&packet).unwrap();
+        json["type"] = (PacketType::ServerStream as i32).into();
+        json["payload"] = serde_json::json!([0x66, 0x6f]);
+
+        let decoded: RpcPacket = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, packet);
+    }
+}
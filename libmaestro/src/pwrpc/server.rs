@@ -0,0 +1,286 @@
+//! RPC server, dispatching incoming calls to registered handlers.
+//!
+//! This allows acting as the device side of the protocol, e.g. for emulating
+//! the firmware of the buds in tests or for injecting synthetic events. Only
+//! unary and server-streaming calls are supported.
+
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use futures::stream::{AbortHandle, BoxStream, SelectAll};
+
+use prost::Message;
+
+use super::client::CallUid;
+use super::status::{Error, Status};
+use super::types::{PacketType, RpcPacket};
+
+
+/// Reply of a method handler to an incoming call.
+pub enum Reply {
+    /// Complete the call with the given response payload.
+    Unary(Bytes),
+
+    /// Send the items of the given stream as server-stream packets and
+    /// complete the call once the stream ends.
+    Stream(BoxStream<'static, Bytes>),
+}
+
+impl Reply {
+    /// Complete the call with the given response message.
+    pub fn unary<M: Message>(message: &M) -> Self {
+        Reply::Unary(message.encode_to_vec().into())
+    }
+
+    /// Send the messages of the given stream and complete the call once it
+    /// ends.
+    pub fn stream<T, M>(messages: T) -> Self
+    where
+        T: Stream<Item = M> + Send + 'static,
+        M: Message,
+    {
+        Reply::Stream(messages.map(|m| m.encode_to_vec().into()).boxed())
+    }
+}
+
+impl std::fmt::Debug for Reply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reply::Unary(payload) => f.debug_tuple("Unary").field(payload).finish(),
+            Reply::Stream(_) => f.debug_tuple("Stream").finish_non_exhaustive(),
+        }
+    }
+}
+
+
+type Handler = Box<dyn FnMut(&RpcPacket) -> Result<Reply, Status> + Send>;
+
+/// Item of an active server stream, `None` marks its end.
+type StreamItem = (CallUid, Option<Bytes>);
+
+
+pub struct Server<S> {
+    /// Lower-level transport.
+    io: S,
+
+    /// Registered method handlers, by service and method ID.
+    methods: HashMap<(u32, u32), Handler>,
+
+    /// Items of all active server streams.
+    streams: SelectAll<BoxStream<'static, StreamItem>>,
+
+    /// Handles for aborting active server streams.
+    active: HashMap<CallUid, AbortHandle>,
+}
+
+impl<S, E> Server<S>
+where
+    S: Sink<RpcPacket>,
+    S: Stream<Item = Result<RpcPacket, E>> + Unpin,
+    Error: From<S::Error>,
+    Error: From<E>,
+{
+    pub fn new(stream: S) -> Server<S> {
+        Server {
+            io: stream,
+            methods: HashMap::new(),
+            streams: SelectAll::new(),
+            active: HashMap::new(),
+        }
+    }
+
+    /// Register a handler for the given method, replacing any handler
+    /// previously registered for it.
+    ///
+    /// The handler is called with the request packet of each incoming call.
+    /// Errors returned by it are sent to the client as server error.
+    pub fn register_method<F>(&mut self, service_id: u32, method_id: u32, handler: F)
+    where
+        F: FnMut(&RpcPacket) -> Result<Reply, Status> + Send + 'static,
+    {
+        self.methods.insert((service_id, method_id), Box::new(handler));
+    }
+
+    /// Run the server, processing incoming calls and active streams.
+    ///
+    /// Returns once the underlying transport has been closed by the client,
+    /// or with an error if a fatal error has occurred.
+    pub async fn run(&mut self) -> Result<(), Error> {
+        loop {
+            tokio::select! {
+                packet = self.io.next() => {
+                    match packet {
+                        Some(packet) => self.process_packet(packet?).await?,
+                        None => return Ok(()),
+                    }
+                },
+                Some((uid, item)) = self.streams.next(), if !self.streams.is_empty() => {
+                    self.process_stream_item(uid, item).await?;
+                },
+            }
+        }
+    }
+
+    async fn process_packet(&mut self, packet: RpcPacket) -> Result<(), Error> {
+        let uid = CallUid::new(packet.channel_id, packet.service_id, packet.method_id, packet.call_id);
+
+        match packet.packet_type() {
+            Some(PacketType::Request) => {
+                // A new request for an active call replaces it.
+                if let Some(handle) = self.active.remove(&uid) {
+                    handle.abort();
+                }
+
+                let Some(handler) = self.methods.get_mut(&(packet.service_id, packet.method_id)) else {
+                    tracing::trace!(
+                        "received request for unknown method: service_id=0x{:08x}, method_id=0x{:08x}",
+                        packet.service_id, packet.method_id,
+                    );
+                    return self.send(reply(uid, PacketType::ServerError, Bytes::new(), Status::NotFound)).await;
+                };
+
+                match handler(&packet) {
+                    Ok(Reply::Unary(payload)) => {
+                        self.send(reply(uid, PacketType::Response, payload, Status::Ok)).await
+                    },
+                    Ok(Reply::Stream(stream)) => {
+                        let stream = stream.map(move |item| (uid, Some(item)))
+                            .chain(futures::stream::once(async move { (uid, None) }));
+
+                        let (stream, handle) = futures::stream::abortable(stream);
+
+                        self.streams.push(stream.boxed());
+                        self.active.insert(uid, handle);
+                        Ok(())
+                    },
+                    Err(status) => {
+                        self.send(reply(uid, PacketType::ServerError, Bytes::new(), status)).await
+                    },
+                }
+            },
+            Some(PacketType::ClientError) => {
+                if let Some(handle) = self.active.remove(&uid) {
+                    tracing::trace!("stream cancelled by client: call_id=0x{:02x}", uid.call_id());
+                    handle.abort();
+                }
+                Ok(())
+            },
+            Some(ty) => {
+                tracing::warn!("unsupported packet type: {}", ty);
+                Ok(())
+            },
+            None => {
+                tracing::warn!("unknown packet type: 0x{:02x}", packet.r#type);
+                Ok(())
+            },
+        }
+    }
+
+    async fn process_stream_item(&mut self, uid: CallUid, item: Option<Bytes>) -> Result<(), Error> {
+        match item {
+            Some(payload) => {
+                self.send(reply(uid, PacketType::ServerStream, payload, Status::Ok)).await
+            },
+            None => {
+                self.active.remove(&uid);
+                self.send(reply(uid, PacketType::Response, Bytes::new(), Status::Ok)).await
+            },
+        }
+    }
+
+    async fn send(&mut self, packet: RpcPacket) -> Result<(), Error> {
+        self.io.send(packet).await?;
+        Ok(())
+    }
+}
+
+impl<S> std::fmt::Debug for Server<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Server")
+            .field("methods", &self.methods.keys().collect::<Vec<_>>())
+            .field("active", &self.active.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+fn reply(uid: CallUid, ty: PacketType, payload: Bytes, status: Status) -> RpcPacket {
+    RpcPacket {
+        r#type: ty.into(),
+        channel_id: uid.channel_id(),
+        service_id: uid.service_id(),
+        method_id: uid.method_id(),
+        payload,
+        status: status.into(),
+        call_id: uid.call_id(),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::protocol::types::{OobeAction, OobeActionRsp, SoftwareInfo};
+    use crate::pwrpc::client::Client;
+    use crate::pwrpc::id::Path;
+    use crate::pwrpc::testing;
+    use crate::service::{MaestroService, MultipointService};
+
+    fn register<F>(server: &mut Server<testing::MockStream>, path: &str, handler: F)
+    where
+        F: FnMut(&RpcPacket) -> Result<Reply, Status> + Send + 'static,
+    {
+        let path = Path::new(path);
+        server.register_method(path.service().hash(), path.method().hash(), handler);
+    }
+
+    #[tokio::test]
+    async fn test_server() {
+        let (client_stream, server_stream) = testing::stream_pair();
+
+        let mut client = Client::new(client_stream);
+        let mut service = MaestroService::new(client.handle(), 18);
+        let mut multipoint = MultipointService::new(client.handle(), 18);
+
+        let info = SoftwareInfo { unknown2: 5, ..Default::default() };
+        let actions = [OobeAction::SingleTap, OobeAction::DoubleTap];
+
+        let mut server = Server::new(server_stream);
+
+        let rsp = info.clone();
+        register(&mut server, "maestro_pw.Maestro/GetSoftwareInfo", move |_| {
+            Ok(Reply::unary(&rsp))
+        });
+
+        register(&mut server, "maestro_pw.Maestro/SubscribeToOobeActions", move |_| {
+            let items = actions.map(|a| OobeActionRsp { action: a as _ });
+            Ok(Reply::stream(futures::stream::iter(items)))
+        });
+
+        let exchange = async {
+            assert_eq!(service.get_software_info().await.unwrap(), info);
+
+            let items: Vec<OobeActionRsp> = service.subscribe_to_oobe_actions().unwrap()
+                .try_collect().await
+                .unwrap();
+            let items: Vec<_> = items.iter().map(|rsp| rsp.action()).collect();
+            assert_eq!(items, actions);
+
+            // unknown method
+            let err = multipoint.subscribe_to_quiet_mode_status().unwrap()
+                .stream().next().await
+                .unwrap()
+                .unwrap_err();
+
+            assert_eq!(err.code(), Status::NotFound);
+            assert!(err.is_remote());
+        };
+
+        tokio::select! {
+            res = server.run() => panic!("server terminated unexpectedly: {res:?}"),
+            _ = testing::run_with_client(&mut client, exchange) => {},
+        }
+    }
+}
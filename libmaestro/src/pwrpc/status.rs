@@ -1,3 +1,9 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::types::CallId;
+
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Status {
     Ok = 0,
@@ -81,11 +87,18 @@ impl From<Status> for u32 {
 }
 
 
-#[derive(Debug)]
+/// RPC error, consisting of a status code and a message.
+///
+/// Errors either originate locally, e.g. from the transport or because a call
+/// has been aborted, or have been reported by the RPC peer. The latter only
+/// carry a status code, as the protocol does not transmit error messages, and
+/// can be identified via [`is_remote`](Self::is_remote).
+#[derive(Debug, Clone)]
 pub struct Error {
     code: Status,
     message: String,
-    source: Option<Box<dyn std::error::Error + Send + Sync + 'static>>,
+    remote: bool,
+    source: Option<Arc<dyn std::error::Error + Send + Sync + 'static>>,
 }
 
 impl Error {
@@ -93,10 +106,19 @@ impl Error {
         Self {
             code,
             message: message.into(),
+            remote: false,
             source: None,
         }
     }
 
+    /// Error with the given status code reported by the RPC peer.
+    pub fn remote(code: Status) -> Self {
+        Self {
+            remote: true,
+            ..Self::new(code, code.description())
+        }
+    }
+
     pub fn cancelled(message: impl Into<String>) -> Self {
         Self::new(Status::Cancelled, message)
     }
@@ -169,7 +191,8 @@ impl Error {
         Self {
             code,
             message: message.into(),
-            source: Some(error.into()),
+            remote: false,
+            source: Some(Arc::from(error.into())),
         }
     }
 
@@ -180,6 +203,31 @@ impl Error {
     pub fn message(&self) -> &str {
         &self.message
     }
+
+    /// Whether this error has been reported by the RPC peer.
+    pub fn is_remote(&self) -> bool {
+        self.remote
+    }
+
+    /// The Bluetooth error this error has been converted from, if any.
+    ///
+    /// Bluetooth errors are mapped to a status code when converting them,
+    /// so this gives access to the original error, e.g. to check its
+    /// [`kind`](bluer::Error::kind).
+    #[cfg(feature = "bluer")]
+    pub fn bluetooth(&self) -> Option<&bluer::Error> {
+        self.source.as_ref()?.downcast_ref()
+    }
+
+    /// Details on the exceeded deadline if this error has been caused by a
+    /// call timing out locally.
+    ///
+    /// In contrast to other errors with status `DeadlineExceeded`, e.g. ones
+    /// reported by the peer, this allows identifying the call and how long it
+    /// has been waited for.
+    pub fn timeout(&self) -> Option<&Timeout> {
+        self.source.as_ref()?.downcast_ref()
+    }
 }
 
 impl From<Status> for Error {
@@ -217,6 +265,39 @@ impl From<std::io::Error> for Error {
     }
 }
 
+#[cfg(feature = "bluer")]
+impl From<bluer::Error> for Error {
+    fn from(err: bluer::Error) -> Self {
+        use bluer::ErrorKind;
+
+        let code = match err.kind {
+            ErrorKind::AlreadyConnected
+            | ErrorKind::AlreadyExists => Status::AlreadyExists,
+            ErrorKind::AuthenticationCanceled => Status::Cancelled,
+            ErrorKind::AuthenticationFailed
+            | ErrorKind::AuthenticationRejected => Status::Unauthenticated,
+            ErrorKind::AuthenticationTimeout => Status::DeadlineExceeded,
+            ErrorKind::ConnectionAttemptFailed
+            | ErrorKind::NotAvailable
+            | ErrorKind::NotReady => Status::Unavailable,
+            ErrorKind::DoesNotExist
+            | ErrorKind::NotFound => Status::NotFound,
+            ErrorKind::InProgress => Status::FailedPrecondition,
+            ErrorKind::InvalidArguments
+            | ErrorKind::InvalidLength
+            | ErrorKind::InvalidAddress(_)
+            | ErrorKind::InvalidName(_) => Status::InvalidArgument,
+            ErrorKind::NotAuthorized
+            | ErrorKind::NotPermitted => Status::PermissionDenied,
+            ErrorKind::NotSupported => Status::Unimplemented,
+            ErrorKind::Internal(_) => Status::Internal,
+            _ => Status::Unknown,
+        };
+
+        Error::extend(code, err.to_string(), err)
+    }
+}
+
 impl From<prost::DecodeError> for Error {
     fn from(error: prost::DecodeError) -> Self {
         Self::extend(Status::InvalidArgument, "failed to decode message", error)
@@ -225,7 +306,7 @@ impl From<prost::DecodeError> for Error {
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "status: {:?}, message: {:?}", self.code, self.message)
+        write!(f, "{} ({:?})", self.message, self.code)
     }
 }
 
@@ -234,3 +315,75 @@ impl std::error::Error for Error {
         self.source.as_ref().map(|err| (&**err) as _)
     }
 }
+
+
+/// A call that did not complete before its deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeout {
+    pub call_id: CallId,
+
+    /// Time passed between starting the call and its deadline expiring.
+    pub elapsed: Duration,
+}
+
+impl std::fmt::Display for Timeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "deadline of {:?} exceeded for call_id={}", self.elapsed, self.call_id)
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+impl From<Timeout> for Error {
+    fn from(timeout: Timeout) -> Self {
+        Self::extend(Status::DeadlineExceeded, timeout.to_string(), timeout)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_error_source() {
+        fn assert_error<E: std::error::Error + Send + Sync + 'static>(_: &E) {}
+
+        let err = Error::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset));
+        assert_error(&err);
+
+        assert_eq!(err.code(), Status::Unavailable);
+        assert_eq!(err.to_string(), "connection reset (Unavailable)");
+
+        let source = std::error::Error::source(&err).unwrap();
+        let source = source.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(source.kind(), std::io::ErrorKind::ConnectionReset);
+
+        let err = Error::from(Status::Aborted);
+        assert_eq!(err.to_string(), "The operation was aborted (Aborted)");
+        assert!(std::error::Error::source(&err).is_none());
+    }
+
+    #[cfg(feature = "bluer")]
+    #[test]
+    fn test_bluetooth_error() {
+        fn connect() -> Result<(), bluer::Error> {
+            Err(bluer::Error {
+                kind: bluer::ErrorKind::NotReady,
+                message: "adapter not powered".into(),
+            })
+        }
+
+        fn call() -> Result<(), Error> {
+            connect()?;
+            Ok(())
+        }
+
+        let err = call().unwrap_err();
+        assert_eq!(err.code(), Status::Unavailable);
+        assert_eq!(err.bluetooth().unwrap().kind, bluer::ErrorKind::NotReady);
+
+        assert!(Error::from(std::io::Error::from(std::io::ErrorKind::NotFound)).bluetooth().is_none());
+        assert!(Error::from(Status::Aborted).bluetooth().is_none());
+    }
+}
@@ -0,0 +1,321 @@
+//! In-memory transport for testing the RPC client without a device.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+
+use futures::{Sink, Stream, StreamExt};
+use futures::channel::mpsc;
+
+use prost::Message;
+
+use super::client::Client;
+use super::status::{Error, Status};
+use super::types::{PacketType, RpcPacket};
+
+
+/// Create a connected pair of mock transport and mock device.
+///
+/// Packets sent by the client via the [`MockStream`] can be received on the
+/// [`MockDevice`] and vice versa.
+pub fn pair() -> (MockStream, MockDevice) {
+    MockDevice::builder().build()
+}
+
+/// Create a connected pair of mock transports.
+///
+/// This allows connecting a [`Client`] to a [`Server`](super::server::Server)
+/// acting as device.
+pub fn stream_pair() -> (MockStream, MockStream) {
+    let (a_tx, b_rx) = mpsc::unbounded();
+    let (b_tx, a_rx) = mpsc::unbounded();
+
+    (MockStream { rx: a_rx, tx: a_tx }, MockStream { rx: b_rx, tx: b_tx })
+}
+
+
+/// End of an in-memory RPC transport, usually on the client side.
+///
+/// Can be passed directly to [`Client::new`](super::client::Client::new).
+#[derive(Debug)]
+pub struct MockStream {
+    rx: mpsc::UnboundedReceiver<RpcPacket>,
+    tx: mpsc::UnboundedSender<RpcPacket>,
+}
+
+impl Stream for MockStream {
+    type Item = Result<RpcPacket, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_next_unpin(cx).map(|packet| packet.map(Ok))
+    }
+}
+
+impl Sink<RpcPacket> for MockStream {
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.tx).poll_ready(cx)
+            .map_err(|_| Error::aborted("mock device disconnected"))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: RpcPacket) -> Result<(), Error> {
+        Pin::new(&mut self.tx).start_send(item)
+            .map_err(|_| Error::aborted("mock device disconnected"))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.tx).poll_flush(cx)
+            .map_err(|_| Error::aborted("mock device disconnected"))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.tx).poll_close(cx)
+            .map_err(|_| Error::aborted("mock device disconnected"))
+    }
+}
+
+
+/// Device-side end of an in-memory RPC transport.
+#[derive(Debug)]
+pub struct MockDevice {
+    rx: mpsc::UnboundedReceiver<RpcPacket>,
+    tx: mpsc::UnboundedSender<RpcPacket>,
+}
+
+impl MockDevice {
+    pub fn builder() -> MockDeviceBuilder {
+        MockDeviceBuilder { packets: Vec::new() }
+    }
+
+    /// Send a packet to the client.
+    pub fn send(&mut self, packet: RpcPacket) -> Result<(), Error> {
+        self.tx.unbounded_send(packet)
+            .map_err(|_| Error::aborted("mock stream disconnected"))
+    }
+
+    /// Receive the next packet sent by the client.
+    ///
+    /// Returns `None` if the client side has been closed.
+    pub async fn recv(&mut self) -> Option<RpcPacket> {
+        self.rx.next().await
+    }
+
+    /// Receive the next packet sent by the client, if one is available
+    /// already.
+    pub fn try_recv(&mut self) -> Option<RpcPacket> {
+        self.rx.try_recv().ok()
+    }
+
+    /// Close the connection to the client, ending its packet stream.
+    pub fn close(&mut self) {
+        self.tx.close_channel();
+    }
+}
+
+
+/// Builder for a [`MockDevice`] with pre-canned packets.
+#[derive(Debug)]
+pub struct MockDeviceBuilder {
+    packets: Vec<RpcPacket>,
+}
+
+impl MockDeviceBuilder {
+    /// Queue a packet to be received by the client once it starts running.
+    pub fn packet(mut self, packet: RpcPacket) -> Self {
+        self.packets.push(packet);
+        self
+    }
+
+    /// Queue a sequence of packets to be received by the client once it
+    /// starts running.
+    pub fn packets(mut self, packets: impl IntoIterator<Item = RpcPacket>) -> Self {
+        self.packets.extend(packets);
+        self
+    }
+
+    pub fn build(self) -> (MockStream, MockDevice) {
+        let (client_tx, device_rx) = mpsc::unbounded();
+        let (device_tx, client_rx) = mpsc::unbounded();
+
+        for packet in self.packets {
+            // SAFETY: We hold the receiver, so the channel cannot be closed.
+            device_tx.unbounded_send(packet).expect("mock channel closed unexpectedly");
+        }
+
+        let stream = MockStream { rx: client_rx, tx: client_tx };
+        let device = MockDevice { rx: device_rx, tx: device_tx };
+
+        (stream, device)
+    }
+}
+
+
+/// Drive the client until the given future completes and return its output.
+///
+/// # Panics
+///
+/// Panics if the client terminates before the future has completed.
+pub async fn run_with_client<S, E, F>(client: &mut Client<S>, fut: F) -> F::Output
+where
+    S: Sink<RpcPacket>,
+    S: Stream<Item = Result<RpcPacket, E>> + Unpin,
+    Error: From<S::Error>,
+    Error: From<E>,
+    F: Future,
+{
+    tokio::select! {
+        res = client.run() => panic!("client terminated unexpectedly: {res:?}"),
+        res = fut => res,
+    }
+}
+
+
+/// Build a response packet completing the call of the given request.
+pub fn response<M: Message>(request: &RpcPacket, message: &M) -> RpcPacket {
+    reply(request, PacketType::Response, message.encode_to_vec().into(), Status::Ok)
+}
+
+/// Build a server-stream packet for the call of the given request.
+pub fn server_stream<M: Message>(request: &RpcPacket, message: &M) -> RpcPacket {
+    reply(request, PacketType::ServerStream, message.encode_to_vec().into(), Status::Ok)
+}
+
+/// Build a server-error packet completing the call of the given request.
+pub fn server_error(request: &RpcPacket, status: Status) -> RpcPacket {
+    reply(request, PacketType::ServerError, Bytes::new(), status)
+}
+
+fn reply(request: &RpcPacket, ty: PacketType, payload: Bytes, status: Status) -> RpcPacket {
+    RpcPacket {
+        r#type: ty.into(),
+        channel_id: request.channel_id,
+        service_id: request.service_id,
+        method_id: request.method_id,
+        payload,
+        status: status.into(),
+        call_id: request.call_id,
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures::StreamExt;
+
+    use crate::protocol::types::{
+        FirmwareInfo, FirmwareVersion, OobeAction, OobeActionRsp, SoftwareInfo,
+    };
+    use crate::pwrpc::id::Path;
+    use crate::service::MaestroService;
+
+    #[tokio::test]
+    async fn test_get_software_info() {
+        let (stream, mut device) = pair();
+
+        let mut client = Client::new(stream);
+        let mut service = MaestroService::new(client.handle(), 18);
+
+        let info = SoftwareInfo {
+            firmware: Some(FirmwareInfo {
+                right: Some(FirmwareVersion {
+                    version_string: "1.2.3".into(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let path = Path::new("maestro_pw.Maestro/GetSoftwareInfo");
+
+        let exchange = async {
+            let fut = service.get_software_info();
+
+            let device = async {
+                let request = device.recv().await.unwrap();
+
+                assert_eq!(request.r#type, PacketType::Request as i32);
+                assert_eq!(request.channel_id, 18);
+                assert_eq!(request.service_id, path.service().hash());
+                assert_eq!(request.method_id, path.method().hash());
+
+                device.send(response(&request, &info)).unwrap();
+            };
+
+            futures::join!(fut, device).0
+        };
+
+        let result = run_with_client(&mut client, exchange).await;
+
+        assert_eq!(result.unwrap(), info);
+        assert!(device.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_combinators() {
+        let (stream, mut device) = pair();
+
+        let mut client = Client::new(stream);
+        let mut service = MaestroService::new(client.handle(), 18);
+
+        let actions = [OobeAction::SingleTap, OobeAction::DoubleTap, OobeAction::TripleTap];
+
+        let exchange = async {
+            // take_while
+            let mut call = service.subscribe_to_oobe_actions().unwrap();
+            let request = device.recv().await.unwrap();
+
+            for action in actions {
+                let msg = OobeActionRsp { action: action.into() };
+                device.send(server_stream(&request, &msg)).unwrap();
+            }
+
+            let items = call.take_while(|rsp| rsp.action() != OobeAction::TripleTap)
+                .map(|rsp| rsp.unwrap().action())
+                .collect::<Vec<_>>()
+                .await;
+
+            assert_eq!(items, [OobeAction::SingleTap, OobeAction::DoubleTap]);
+
+            // the subscription does not end on its own, cancel it and check
+            // that the device is notified before starting the next one
+            call.cancel_and_wait().await.unwrap();
+
+            let cancel = device.recv().await.unwrap();
+            assert_eq!(cancel.r#type, PacketType::ClientError as i32);
+
+            // try_collect
+            let call = service.subscribe_to_oobe_actions().unwrap();
+            let request = device.recv().await.unwrap();
+
+            for action in actions {
+                let msg = OobeActionRsp { action: action.into() };
+                device.send(server_stream(&request, &msg)).unwrap();
+            }
+            device.send(response(&request, &())).unwrap();
+
+            let items: Vec<OobeActionRsp> = call.try_collect().await.unwrap();
+            let items: Vec<_> = items.iter().map(|rsp| rsp.action()).collect();
+
+            assert_eq!(items, actions);
+        };
+
+        run_with_client(&mut client, exchange).await;
+    }
+
+    #[tokio::test]
+    async fn test_run_error() {
+        let (stream, mut device) = pair();
+        let mut client = Client::new(stream);
+
+        device.close();
+
+        let err = client.run().await.unwrap_err();
+        assert_eq!(err.code(), Status::Aborted);
+    }
+}
@@ -1,3 +1,8 @@
+use bytes::Bytes;
+
+use super::status::{Error, Status};
+
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RpcType {
     Unary,
@@ -23,9 +28,246 @@ impl RpcType {
 }
 
 
+macro_rules! id_type {
+    ($(#[$meta:meta])* $name:ident, $fmt:literal) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(pub u32);
+
+        impl From<u32> for $name {
+            fn from(id: u32) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<$name> for u32 {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, $fmt, self.0)
+            }
+        }
+    };
+}
+
+id_type! {
+    /// Call identifier, distinguishing multiple calls to the same method.
+    CallId, "0x{:02x}"
+}
+
+id_type! {
+    /// Service identifier, i.e., the hash of the fully qualified service name.
+    ServiceId, "0x{:08x}"
+}
+
+id_type! {
+    /// Method identifier, i.e., the hash of the method name.
+    MethodId, "0x{:08x}"
+}
+
+
 mod generated {
     include!(concat!(env!("OUT_DIR"), "/pw.rpc.packet.rs"));
 }
 
 pub use generated::PacketType;
 pub use generated::RpcPacket;
+
+impl RpcPacket {
+    pub fn builder() -> RpcPacketBuilder {
+        RpcPacketBuilder::default()
+    }
+
+    /// Return the size of the payload in bytes.
+    pub fn payload_len(&self) -> usize {
+        self.payload.len()
+    }
+
+    /// Return the type of this packet, or `None` if it is not known.
+    ///
+    /// In contrast to the generated `r#type()` accessor, this does not fall
+    /// back to `PacketType::Request` for unknown values.
+    pub fn packet_type(&self) -> Option<PacketType> {
+        PacketType::from_i32(self.r#type)
+    }
+}
+
+impl std::fmt::Display for PacketType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PacketType::Request => "request",
+            PacketType::ClientStream => "client stream",
+            PacketType::ClientError => "client error",
+            PacketType::DeprecatedCancel => "cancel (deprecated)",
+            PacketType::ClientStreamEnd => "client stream end",
+            PacketType::Response => "response",
+            PacketType::DeprecatedServerStreamEnd => "server stream end (deprecated)",
+            PacketType::ServerError => "server error",
+            PacketType::ServerStream => "server stream",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+
+/// Builder for [`RpcPacket`]s.
+///
+/// The packet type, channel ID, service ID, and method ID are required. The
+/// call ID defaults to zero, the payload to empty, and the status to `Ok`.
+#[derive(Debug, Clone)]
+pub struct RpcPacketBuilder {
+    packet_type: Option<PacketType>,
+    channel_id: Option<u32>,
+    service_id: Option<u32>,
+    method_id: Option<u32>,
+    call_id: u32,
+    payload: Bytes,
+    status: Status,
+}
+
+impl Default for RpcPacketBuilder {
+    fn default() -> Self {
+        Self {
+            packet_type: None,
+            channel_id: None,
+            service_id: None,
+            method_id: None,
+            call_id: 0,
+            payload: Bytes::new(),
+            status: Status::Ok,
+        }
+    }
+}
+
+impl RpcPacketBuilder {
+    pub fn packet_type(mut self, ty: PacketType) -> Self {
+        self.packet_type = Some(ty);
+        self
+    }
+
+    pub fn channel_id(mut self, id: u32) -> Self {
+        self.channel_id = Some(id);
+        self
+    }
+
+    pub fn service_id(mut self, id: impl Into<ServiceId>) -> Self {
+        self.service_id = Some(id.into().into());
+        self
+    }
+
+    pub fn method_id(mut self, id: impl Into<MethodId>) -> Self {
+        self.method_id = Some(id.into().into());
+        self
+    }
+
+    pub fn call_id(mut self, id: impl Into<CallId>) -> Self {
+        self.call_id = id.into().into();
+        self
+    }
+
+    pub fn payload(mut self, payload: impl Into<Bytes>) -> Self {
+        self.payload = payload.into();
+        self
+    }
+
+    pub fn status(mut self, status: Status) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Build the packet.
+    ///
+    /// Fails with [`Status::InvalidArgument`] if any of the required fields
+    /// has not been set.
+    pub fn build(self) -> Result<RpcPacket, Error> {
+        fn required<T>(value: Option<T>, name: &str) -> Result<T, Error> {
+            value.ok_or_else(|| Error::invalid_argument(format!("packet {name} not set")))
+        }
+
+        Ok(RpcPacket {
+            r#type: required(self.packet_type, "type")?.into(),
+            channel_id: required(self.channel_id, "channel ID")?,
+            service_id: required(self.service_id, "service ID")?,
+            method_id: required(self.method_id, "method ID")?,
+            payload: self.payload,
+            status: self.status.into(),
+            call_id: self.call_id,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_packet_type() {
+        let packet = RpcPacket { r#type: PacketType::ServerStream.into(), ..Default::default() };
+        assert_eq!(packet.packet_type(), Some(PacketType::ServerStream));
+        assert_eq!(PacketType::ServerStream.to_string(), "server stream");
+
+        let packet = RpcPacket { r#type: 42, ..Default::default() };
+        assert_eq!(packet.packet_type(), None);
+    }
+
+    #[test]
+    fn test_packet_builder() {
+        let packet = RpcPacket::builder()
+            .packet_type(PacketType::Request)
+            .channel_id(18)
+            .service_id(1)
+            .method_id(2)
+            .call_id(3)
+            .payload(vec![4, 5])
+            .build()
+            .unwrap();
+
+        let expected = RpcPacket {
+            r#type: PacketType::Request.into(),
+            channel_id: 18,
+            service_id: 1,
+            method_id: 2,
+            payload: Bytes::from_static(&[4, 5]),
+            status: Status::Ok.into(),
+            call_id: 3,
+        };
+        assert_eq!(packet, expected);
+
+        let err = RpcPacket::builder()
+            .packet_type(PacketType::Request)
+            .channel_id(18)
+            .method_id(2)
+            .build()
+            .unwrap_err();
+
+        assert_eq!(err.code(), Status::InvalidArgument);
+        assert_eq!(err.message(), "packet service ID not set");
+    }
+
+    #[test]
+    fn test_ids() {
+        assert_eq!(CallId::from(3), CallId(3));
+        assert_eq!(u32::from(ServiceId(0x7ede71ea)), 0x7ede71ea);
+
+        assert_eq!(CallId(3).to_string(), "0x03");
+        assert_eq!(ServiceId(0x7ede71ea).to_string(), "0x7ede71ea");
+        assert_eq!(MethodId(2).to_string(), "0x00000002");
+
+        let packet = RpcPacket::builder()
+            .packet_type(PacketType::Request)
+            .channel_id(18)
+            .service_id(ServiceId(1))
+            .method_id(MethodId(2))
+            .call_id(CallId(3))
+            .build()
+            .unwrap();
+
+        assert_eq!((packet.service_id, packet.method_id, packet.call_id), (1, 2, 3));
+    }
+}
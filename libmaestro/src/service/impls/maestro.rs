@@ -1,10 +1,19 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::StreamExt;
+use futures::stream::BoxStream;
+
+use tokio::time::Instant;
+
 use crate::protocol::types::{
-    self, read_setting_msg, settings_rsp, write_setting_msg, HardwareInfo, OobeActionRsp,
+    read_setting_msg, BatteryInfo, HardwareInfo, OobeActionRsp,
     ReadSettingMsg, RuntimeInfo, SettingsRsp, SoftwareInfo, WriteSettingMsg,
 };
 use crate::pwrpc::client::{ClientHandle, ServerStreamRpc, StreamResponse, UnaryRpc};
 use crate::pwrpc::Error;
-use crate::service::settings::{Setting, SettingId, SettingValue};
+use crate::service::ohd::OnHeadState;
+use crate::service::settings::{self, AncState, GestureControl, Setting, SettingId, SettingValue, SettingsBuilder};
 
 
 #[derive(Debug, Clone)]
@@ -21,6 +30,9 @@ pub struct MaestroService {
     rpc_sub_settings_changes: ServerStreamRpc<(), SettingsRsp>,
 
     rpc_sub_oobe_actions: ServerStreamRpc<(), OobeActionRsp>,
+
+    /// Last known auto-pause state, shared between clones of this service.
+    auto_pause: Arc<Mutex<Option<bool>>>,
 }
 
 impl MaestroService {
@@ -38,42 +50,88 @@ impl MaestroService {
             rpc_sub_settings_changes: ServerStreamRpc::new("maestro_pw.Maestro/SubscribeToSettingsChanges"),
 
             rpc_sub_oobe_actions: ServerStreamRpc::new("maestro_pw.Maestro/SubscribeToOobeActions"),
+
+            auto_pause: Arc::new(Mutex::new(None)),
         }
     }
 
     pub async fn get_software_info(&mut self) -> Result<SoftwareInfo, Error> {
-        self.rpc_get_software_info.call(&mut self.client, self.channel_id, 0, ())?
+        let call_id = self.client.next_call_id();
+        self.rpc_get_software_info.call(&mut self.client, self.channel_id, call_id, ())?
             .result().await
     }
 
+    /// Measure the round-trip time of a call.
+    ///
+    /// There is no dedicated echo call, so this measures the time it takes to
+    /// retrieve the software information, which is answered immediately by
+    /// the device.
+    pub async fn ping(&mut self) -> Result<Duration, Error> {
+        let start = Instant::now();
+        self.get_software_info().await?;
+
+        Ok(start.elapsed())
+    }
+
     pub async fn get_hardware_info(&mut self) -> Result<HardwareInfo, Error> {
-        self.rpc_get_hardware_info.call(&mut self.client, self.channel_id, 0, ())?
+        let call_id = self.client.next_call_id();
+        self.rpc_get_hardware_info.call(&mut self.client, self.channel_id, call_id, ())?
             .result().await
     }
 
+    /// Subscribe to runtime information updates, including battery status
+    /// and placement of the buds.
     pub fn subscribe_to_runtime_info(&mut self) -> Result<StreamResponse<RuntimeInfo>, Error> {
-        self.rpc_sub_runtime_info.call(&mut self.client, self.channel_id, 0, ())
+        let call_id = self.client.next_call_id();
+        self.rpc_sub_runtime_info.call(&mut self.client, self.channel_id, call_id, ())
+    }
+
+    /// Get the current runtime information.
+    ///
+    /// There is no dedicated call for this, so this subscribes to runtime
+    /// information updates and returns the first one, which is sent by the
+    /// device immediately.
+    pub async fn get_runtime_info(&mut self) -> Result<RuntimeInfo, Error> {
+        let mut call = self.subscribe_to_runtime_info()?;
+
+        call.stream().next().await
+            .ok_or_else(|| Error::aborted("stream terminated without item"))?
+    }
+
+    /// Get the current battery status of case and buds.
+    pub async fn get_battery_info(&mut self) -> Result<BatteryInfo, Error> {
+        self.get_runtime_info().await?
+            .battery_info
+            .ok_or_else(|| Error::invalid_argument("did not receive any battery info"))
     }
 
     pub async fn write_setting_raw(&mut self, setting: WriteSettingMsg) -> Result<(), Error> {
-        self.rpc_write_setting.call(&mut self.client, self.channel_id, 0, setting)?
+        let call_id = self.client.next_call_id();
+        self.rpc_write_setting.call(&mut self.client, self.channel_id, call_id, setting)?
             .result().await
     }
 
     pub async fn write_setting(&mut self, setting: SettingValue) -> Result<(), Error> {
-        let setting = types::SettingValue {
-            value_oneof: Some(setting.into()),
-        };
+        self.write_setting_raw(setting.clone().into()).await?;
+        self.update_cache(&setting);
+        Ok(())
+    }
 
-        let setting = WriteSettingMsg {
-            value_oneof: Some(write_setting_msg::ValueOneof::Setting(setting)),
-        };
+    /// Write all settings of the given builder, one after another.
+    ///
+    /// Stops at the first setting that fails to be written. Settings written
+    /// before that are not reverted.
+    pub async fn write_settings(&mut self, settings: SettingsBuilder) -> Result<(), Error> {
+        for setting in settings.build_values()? {
+            self.write_setting(setting).await?;
+        }
 
-        self.write_setting_raw(setting).await
+        Ok(())
     }
 
     pub async fn read_setting_raw(&mut self, setting: ReadSettingMsg) -> Result<SettingsRsp, Error> {
-        self.rpc_read_setting.call(&mut self.client, self.channel_id, 0, setting)?
+        let call_id = self.client.next_call_id();
+        self.rpc_read_setting.call(&mut self.client, self.channel_id, call_id, setting)?
             .result().await
     }
 
@@ -81,17 +139,12 @@ impl MaestroService {
         let setting = read_setting_msg::ValueOneof::SettingsId(setting.into());
         let setting = ReadSettingMsg { value_oneof: Some(setting) };
 
-        let value = self.read_setting_raw(setting).await?;
-
-        let value = value.value_oneof
+        let value = self.read_setting_raw(setting).await?
+            .value()
             .ok_or_else(|| Error::invalid_argument("did not receive any settings value"))?;
 
-        let settings_rsp::ValueOneof::Value(value) = value;
-
-        let value = value.value_oneof
-            .ok_or_else(|| Error::invalid_argument("did not receive any settings value"))?;
-
-        Ok(value.into())
+        self.update_cache(&value);
+        Ok(value)
     }
 
     pub async fn read_setting<T>(&mut self, setting: T) -> Result<T::Type, Error>
@@ -104,14 +157,331 @@ impl MaestroService {
             .ok_or_else(|| Error::invalid_argument("failed to decode settings value"))
     }
 
+    pub async fn set_anc_state(&mut self, state: AncState) -> Result<(), Error> {
+        self.write_setting(SettingValue::CurrentAncrState(state)).await
+    }
+
+    /// Read the actions currently assigned to the hold gesture of each bud.
+    ///
+    /// Use [`GestureControl::diff`] to compare them to the actions about to
+    /// be written.
+    pub async fn get_gesture_control(&mut self) -> Result<GestureControl, Error> {
+        self.read_setting(settings::id::GestureControl).await
+    }
+
+    pub async fn set_ohd_enabled(&mut self, enabled: bool) -> Result<(), Error> {
+        self.write_setting(SettingValue::OhdEnable(enabled)).await
+    }
+
+    /// Enable or disable pausing audio when the buds are taken out.
+    ///
+    /// This is controlled by on-head detection (OHD), i.e., the same as
+    /// [`set_ohd_enabled`](Self::set_ohd_enabled).
+    pub async fn set_auto_pause_enabled(&mut self, enabled: bool) -> Result<(), Error> {
+        self.set_ohd_enabled(enabled).await
+    }
+
+    /// Read whether audio is paused when the buds are taken out.
+    pub async fn get_auto_pause_enabled(&mut self) -> Result<bool, Error> {
+        self.read_setting(settings::id::OhdEnable).await
+    }
+
+    /// Return the auto-pause state last read from or written to the device
+    /// via this service or any of its clones, without querying the device.
+    ///
+    /// Changes made by other means, e.g. via the Google Buds app, are not
+    /// reflected until the setting is read again.
+    pub fn cached_auto_pause(&self) -> Option<bool> {
+        *self.auto_pause.lock().unwrap()
+    }
+
+    /// Enable or disable multipoint audio, i.e., being connected to two
+    /// source devices at the same time.
+    ///
+    /// The device may change other settings as a side effect of this, e.g.
+    /// the ANC state. To detect this, the ANC state is read before and after
+    /// changing the setting, and a warning is logged if it has changed.
+    pub async fn set_multipoint_enabled(&mut self, enabled: bool) -> Result<(), Error> {
+        let anc_before = self.read_setting(settings::id::CurrentAncrState).await?;
+
+        self.write_setting(SettingValue::MultipointEnable(enabled)).await?;
+
+        let anc_after = self.read_setting(settings::id::CurrentAncrState).await?;
+        if anc_after != anc_before {
+            tracing::warn!(
+                "device changed ANC state from '{}' to '{}' when {} multipoint audio",
+                anc_before, anc_after, if enabled { "enabling" } else { "disabling" },
+            );
+        }
+
+        Ok(())
+    }
+
+    fn update_cache(&self, value: &SettingValue) {
+        if let SettingValue::OhdEnable(enabled) = value {
+            *self.auto_pause.lock().unwrap() = Some(*enabled);
+        }
+    }
+
     pub fn subscribe_to_settings_changes(&mut self) -> Result<StreamResponse<SettingsRsp>, Error> {
-        self.rpc_sub_settings_changes.call(&mut self.client, self.channel_id, 0, ())
+        let call_id = self.client.next_call_id();
+        self.rpc_sub_settings_changes.call(&mut self.client, self.channel_id, call_id, ())
     }
 
+    /// Subscribe to out-of-box experience (OOBE) actions, i.e., gestures
+    /// (taps, holds, swipes) and on-head detection events.
+    ///
+    /// Use [`OobeActionRsp::action`] to obtain the typed action. Apart from
+    /// on-head detection events, actions do not indicate which bud they
+    /// originate from.
     pub fn subscribe_to_oobe_actions(&mut self) -> Result<StreamResponse<OobeActionRsp>, Error> {
-        self.rpc_sub_oobe_actions.call(&mut self.client, self.channel_id, 0, ())
+        let call_id = self.client.next_call_id();
+        self.rpc_sub_oobe_actions.call(&mut self.client, self.channel_id, call_id, ())
+    }
+
+    /// Subscribe to changes of the on-head state of the buds.
+    ///
+    /// Each on-head detection event yields the updated state, see
+    /// [`OnHeadState`]. Other OOBE actions are skipped. Dropping the stream
+    /// cancels the subscription.
+    pub fn subscribe_to_on_head_state(&mut self) -> Result<BoxStream<'static, Result<OnHeadState, Error>>, Error> {
+        let mut state = OnHeadState::default();
+
+        let stream = self.subscribe_to_oobe_actions()?
+            .into_stream()
+            .filter_map(move |item| {
+                let item = match item {
+                    Ok(rsp) => state.update(rsp.action()).then_some(Ok(state)),
+                    Err(err) => Some(Err(err)),
+                };
+
+                futures::future::ready(item)
+            });
+
+        Ok(stream.boxed())
+    }
+
+    /// Subscribe to all known event streams of this service at once.
+    ///
+    /// Events of the individual subscriptions are merged into a single
+    /// stream, which ends once all subscriptions have ended. Dropping the
+    /// stream cancels all subscriptions.
+    pub fn subscribe_to_all_events(&mut self) -> Result<BoxStream<'static, Result<MaestroEvent, Error>>, Error> {
+        fn events<M, F>(name: &'static str, call: StreamResponse<M>, map: F)
+            -> BoxStream<'static, Result<MaestroEvent, Error>>
+        where
+            M: prost::Message + Default + Send + 'static,
+            F: Fn(M) -> MaestroEvent + Send + 'static,
+        {
+            let end = futures::stream::once(async move {
+                tracing::debug!("{} subscription ended", name);
+                None
+            });
+
+            call.into_stream()
+                .map(move |item| Some(item.map(&map)))
+                .chain(end)
+                .filter_map(futures::future::ready)
+                .boxed()
+        }
+
+        let streams = [
+            events("settings", self.subscribe_to_settings_changes()?, MaestroEvent::Settings),
+            events("runtime info", self.subscribe_to_runtime_info()?, MaestroEvent::RuntimeInfo),
+            events("OOBE action", self.subscribe_to_oobe_actions()?, MaestroEvent::OobeAction),
+        ];
+
+        Ok(futures::stream::select_all(streams).boxed())
     }
 
     // TODO:
     // - SetWallClock
 }
+
+
+/// Event received via one of the subscriptions of the [`MaestroService`].
+///
+/// See [`MaestroService::subscribe_to_all_events`].
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "snake_case"))]
+pub enum MaestroEvent {
+    /// A setting has changed.
+    Settings(SettingsRsp),
+
+    /// Runtime information, including battery status and placement of the
+    /// buds, has changed.
+    RuntimeInfo(RuntimeInfo),
+
+    /// A gesture or on-head detection event has occurred.
+    OobeAction(OobeActionRsp),
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::pwrpc::client::Client;
+    use crate::pwrpc::testing;
+
+    fn setting_rsp(value: SettingValue) -> SettingsRsp {
+        let value = crate::protocol::types::SettingValue {
+            value_oneof: Some(value.into()),
+        };
+
+        SettingsRsp {
+            value_oneof: Some(crate::protocol::types::settings_rsp::ValueOneof::Value(value)),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ping() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut service = MaestroService::new(client.handle(), 18);
+
+        let exchange = async {
+            let device = async {
+                let request = device.recv().await.unwrap();
+
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                device.send(testing::response(&request, &SoftwareInfo::default())).unwrap();
+            };
+
+            futures::join!(service.ping(), device).0
+        };
+
+        let rtt = testing::run_with_client(&mut client, exchange).await;
+        assert_eq!(rtt.unwrap(), Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_to_all_events() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut service = MaestroService::new(client.handle(), 18);
+
+        let mut events = service.subscribe_to_all_events().unwrap();
+
+        let rtinfo = RuntimeInfo { timestamp_ms: 42, ..Default::default() };
+        let action = OobeActionRsp { action: crate::protocol::types::OobeAction::DoubleTap as _ };
+
+        let exchange = async {
+            let mut requests = Vec::new();
+            for _ in 0..3 {
+                requests.push(device.recv().await.unwrap());
+            }
+
+            let request = |path: &str| {
+                let path = crate::pwrpc::id::Path::new(path);
+                requests.iter()
+                    .find(|r| r.method_id == path.method().hash())
+                    .unwrap()
+                    .clone()
+            };
+
+            let req_settings = request("maestro_pw.Maestro/SubscribeToSettingsChanges");
+            let req_rtinfo = request("maestro_pw.Maestro/SubscribeRuntimeInfo");
+            let req_oobe = request("maestro_pw.Maestro/SubscribeToOobeActions");
+
+            device.send(testing::server_stream(&req_rtinfo, &rtinfo)).unwrap();
+            device.send(testing::server_stream(&req_oobe, &action)).unwrap();
+            device.send(testing::response(&req_settings, &())).unwrap();
+
+            let mut items = Vec::new();
+            for _ in 0..2 {
+                items.push(events.next().await.unwrap().unwrap());
+            }
+            items
+        };
+
+        let items = testing::run_with_client(&mut client, exchange).await;
+
+        assert_eq!(items.len(), 2);
+        assert!(items.contains(&MaestroEvent::RuntimeInfo(rtinfo)));
+        assert!(items.contains(&MaestroEvent::OobeAction(action)));
+    }
+
+    #[tokio::test]
+    async fn test_auto_pause() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut service = MaestroService::new(client.handle(), 18);
+        let observer = service.clone();
+
+        assert_eq!(observer.cached_auto_pause(), None);
+
+        let exchange = async {
+            let write = async {
+                let request = device.recv().await.unwrap();
+                device.send(testing::response(&request, &())).unwrap();
+            };
+
+            futures::join!(service.set_auto_pause_enabled(false), write).0.unwrap();
+            assert_eq!(observer.cached_auto_pause(), Some(false));
+
+            let read = async {
+                let request = device.recv().await.unwrap();
+                device.send(testing::response(&request, &setting_rsp(SettingValue::OhdEnable(true)))).unwrap();
+            };
+
+            let enabled = futures::join!(service.get_auto_pause_enabled(), read).0;
+
+            let write = async {
+                let request = device.recv().await.unwrap();
+                device.send(testing::response(&request, &())).unwrap();
+            };
+
+            let settings = SettingsBuilder::new().ohd_enable(false);
+            futures::join!(service.write_settings(settings), write).0.unwrap();
+
+            enabled
+        };
+
+        let enabled = testing::run_with_client(&mut client, exchange).await;
+        assert!(enabled.unwrap());
+        assert_eq!(observer.cached_auto_pause(), Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_set_multipoint_enabled() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut service = MaestroService::new(client.handle(), 18);
+
+        let exchange = async {
+            let responder = async {
+                let mut methods = Vec::new();
+
+                let request = device.recv().await.unwrap();
+                device.send(testing::response(&request, &setting_rsp(SettingValue::CurrentAncrState(AncState::Active)))).unwrap();
+                methods.push(request.method_id);
+
+                let request = device.recv().await.unwrap();
+                device.send(testing::response(&request, &())).unwrap();
+                methods.push(request.method_id);
+
+                // the device has changed the ANC state as side effect
+                let request = device.recv().await.unwrap();
+                device.send(testing::response(&request, &setting_rsp(SettingValue::CurrentAncrState(AncState::Off)))).unwrap();
+                methods.push(request.method_id);
+
+                methods
+            };
+
+            futures::join!(service.set_multipoint_enabled(true), responder)
+        };
+
+        let (result, methods) = testing::run_with_client(&mut client, exchange).await;
+        result.unwrap();
+
+        let read = crate::pwrpc::id::Path::new("maestro_pw.Maestro/ReadSetting").method().hash();
+        let write = crate::pwrpc::id::Path::new("maestro_pw.Maestro/WriteSetting").method().hash();
+        assert_eq!(methods, [read, write, read]);
+    }
+}
@@ -1,5 +1,5 @@
 mod maestro;
-pub use self::maestro::MaestroService;
+pub use self::maestro::{MaestroEvent, MaestroService};
 
 mod multipoint;
 pub use self::multipoint::MultipointService;
@@ -22,7 +22,8 @@ impl MultipointService {
     }
 
     pub fn subscribe_to_quiet_mode_status(&mut self) -> Result<StreamResponse<QuietModeStatusEvent>, Error> {
-        self.rpc_sub_quiet_mode_status.call(&mut self.client, self.channel_id, 0, ())
+        let call_id = self.client.next_call_id();
+        self.rpc_sub_quiet_mode_status.call(&mut self.client, self.channel_id, call_id, ())
     }
 
     // TODO:
@@ -0,0 +1,114 @@
+//! Definitions of known unary methods for use with
+//! [`ClientHandle::call`](crate::pwrpc::client::ClientHandle::call).
+//!
+//! Streaming methods are not covered here, see the service implementations
+//! for those. The names of all known methods, including streaming ones, are
+//! available via [`registry`], e.g. for describing packets in log messages.
+
+use crate::protocol::types::{
+    HardwareInfo, ReadSettingMsg, SettingsRsp, SoftwareInfo, WriteSettingMsg,
+};
+use crate::pwrpc::client::RpcMethod;
+use crate::pwrpc::id::{IdRef, MethodRegistry};
+
+
+macro_rules! rpc_method {
+    ($name:ident, $service:literal, $method:literal, $req:ty, $rsp:ty) => {
+        #[doc = concat!("The `", $service, "/", $method, "` method.")]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub struct $name;
+
+        impl RpcMethod for $name {
+            const SERVICE_ID: u32 = IdRef::new($service).hash();
+            const METHOD_ID: u32 = IdRef::new($method).hash();
+
+            type Request = $req;
+            type Response = $rsp;
+        }
+    };
+}
+
+rpc_method!(GetSoftwareInfo, "maestro_pw.Maestro", "GetSoftwareInfo", (), SoftwareInfo);
+rpc_method!(GetHardwareInfo, "maestro_pw.Maestro", "GetHardwareInfo", (), HardwareInfo);
+rpc_method!(WriteSetting, "maestro_pw.Maestro", "WriteSetting", WriteSettingMsg, ());
+rpc_method!(ReadSetting, "maestro_pw.Maestro", "ReadSetting", ReadSettingMsg, SettingsRsp);
+
+
+/// Paths of all known methods.
+pub const KNOWN_METHODS: &[&str] = &[
+    "maestro_pw.Maestro/GetSoftwareInfo",
+    "maestro_pw.Maestro/GetHardwareInfo",
+    "maestro_pw.Maestro/SubscribeRuntimeInfo",
+    "maestro_pw.Maestro/SetWallClock",
+    "maestro_pw.Maestro/WriteSetting",
+    "maestro_pw.Maestro/ReadSetting",
+    "maestro_pw.Maestro/SubscribeToSettingsChanges",
+    "maestro_pw.Maestro/SubscribeToOobeActions",
+    "maestro_pw.Multipoint/SubscribeToQuietModeStatus",
+    "maestro_pw.Multipoint/ForceMultipointSwitch",
+    "maestro_pw.EartipFitTest/StartTest",
+    "maestro_pw.EartipFitTest/EndTest",
+    "maestro_pw.EartipFitTest/SubscribeToResults",
+];
+
+/// Create a registry containing all known methods.
+pub fn registry() -> MethodRegistry {
+    KNOWN_METHODS.iter().copied().collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::pwrpc::client::Client;
+    use crate::pwrpc::id::Path;
+    use crate::pwrpc::testing;
+
+    #[test]
+    fn test_ids() {
+        let path = Path::new("maestro_pw.Maestro/ReadSetting");
+
+        assert_eq!(ReadSetting::SERVICE_ID, path.service().hash());
+        assert_eq!(ReadSetting::METHOD_ID, path.method().hash());
+    }
+
+    #[test]
+    fn test_registry() {
+        let registry = registry();
+
+        // no collisions between known methods
+        assert_eq!(registry.len(), KNOWN_METHODS.len());
+
+        let path = registry.lookup(ReadSetting::SERVICE_ID, ReadSetting::METHOD_ID).unwrap();
+        assert_eq!(path.as_str(), "maestro_pw.Maestro/ReadSetting");
+    }
+
+    #[tokio::test]
+    async fn test_call() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+
+        let info = HardwareInfo { unknown1: 7, ..Default::default() };
+
+        let exchange = async {
+            let call = handle.call::<GetHardwareInfo>(18, ());
+
+            let device = async {
+                let request = device.recv().await.unwrap();
+
+                assert_eq!(request.service_id, GetHardwareInfo::SERVICE_ID);
+                assert_eq!(request.method_id, GetHardwareInfo::METHOD_ID);
+
+                device.send(testing::response(&request, &info)).unwrap();
+            };
+
+            futures::join!(call, device).0
+        };
+
+        let result = testing::run_with_client(&mut client, exchange).await;
+        assert_eq!(result.unwrap(), info);
+    }
+}
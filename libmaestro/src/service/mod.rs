@@ -1,4 +1,6 @@
+pub mod methods;
+pub mod ohd;
 pub mod settings;
 
 mod impls;
-pub use impls::{MaestroService, MultipointService};
+pub use impls::{MaestroEvent, MaestroService, MultipointService};
@@ -0,0 +1,94 @@
+//! On-head detection (OHD) state tracking.
+//!
+//! On-head detection events are sent as out-of-box experience (OOBE) actions,
+//! see [`MaestroService::subscribe_to_oobe_actions`], and tracked via
+//! [`MaestroService::subscribe_to_on_head_state`]. Note that these events are
+//! only sent on change, so the state of a bud is unknown until the first
+//! event for it has been received.
+//!
+//! [`MaestroService::subscribe_to_oobe_actions`]: super::MaestroService::subscribe_to_oobe_actions
+//! [`MaestroService::subscribe_to_on_head_state`]: super::MaestroService::subscribe_to_on_head_state
+
+use crate::protocol::types::OobeAction;
+
+
+/// On-head state of both buds, `None` if unknown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OnHeadState {
+    pub left: Option<bool>,
+    pub right: Option<bool>,
+}
+
+impl OnHeadState {
+    /// Update the state based on the given action.
+    ///
+    /// Returns `true` if the action is an on-head detection event, `false`
+    /// otherwise. The state is left unchanged for any other actions.
+    pub fn update(&mut self, action: OobeAction) -> bool {
+        match action {
+            OobeAction::LeftOnHead => self.left = Some(true),
+            OobeAction::LeftOffHead => self.left = Some(false),
+            OobeAction::RightOnHead => self.right = Some(true),
+            OobeAction::RightOffHead => self.right = Some(false),
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use futures::StreamExt;
+
+    use super::*;
+
+    use crate::protocol::types::OobeActionRsp;
+    use crate::pwrpc::client::Client;
+    use crate::pwrpc::testing;
+    use crate::service::MaestroService;
+
+    #[tokio::test]
+    async fn test_on_head_events() {
+        let (stream, mut device) = testing::pair();
+
+        let mut client = Client::new(stream);
+        let mut service = MaestroService::new(client.handle(), 18);
+
+        let actions = [
+            OobeAction::LeftOnHead,
+            OobeAction::RightOnHead,
+            OobeAction::SingleTap,
+            OobeAction::LeftOffHead,
+            OobeAction::RightOffHead,
+        ];
+
+        let expected = [
+            OnHeadState { left: Some(true), right: None },
+            OnHeadState { left: Some(true), right: Some(true) },
+            OnHeadState { left: Some(false), right: Some(true) },
+            OnHeadState { left: Some(false), right: Some(false) },
+        ];
+
+        let stream = service.subscribe_to_on_head_state().unwrap();
+
+        let exchange = async {
+            let request = device.recv().await.unwrap();
+
+            for action in actions {
+                let msg = OobeActionRsp { action: action.into() };
+                device.send(testing::server_stream(&request, &msg)).unwrap();
+            }
+
+            stream.take(expected.len())
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+                .await
+        };
+
+        let states = testing::run_with_client(&mut client, exchange).await;
+
+        assert_eq!(states, expected);
+    }
+}
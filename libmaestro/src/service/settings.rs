@@ -1,10 +1,13 @@
 use num_enum::{IntoPrimitive, FromPrimitive};
 
 use crate::protocol::types;
+use crate::pwrpc::Error;
 
 
+#[non_exhaustive]
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum SettingId {
     AutoOtaEnable = 1,
     OhdEnable = 2,
@@ -30,7 +33,9 @@ pub enum SettingId {
 }
 
 
+#[non_exhaustive]
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum SettingValue {
     AutoOtaEnable(bool),
     OhdEnable(bool),
@@ -69,6 +74,27 @@ impl SettingValue {
     }
 }
 
+impl std::fmt::Display for SettingValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingValue::AutoOtaEnable(x) => write!(f, "auto OTA enabled: {}", x),
+            SettingValue::OhdEnable(x) => write!(f, "on-head detection enabled: {}", x),
+            SettingValue::OobeIsFinished(x) => write!(f, "OOBE finished: {}", x),
+            SettingValue::GestureEnable(x) => write!(f, "gestures enabled: {}", x),
+            SettingValue::DiagnosticsEnable(x) => write!(f, "diagnostics enabled: {}", x),
+            SettingValue::OobeMode(x) => write!(f, "OOBE mode: {}", x),
+            SettingValue::GestureControl(x) => write!(f, "gesture control: {}", x),
+            SettingValue::MultipointEnable(x) => write!(f, "multipoint enabled: {}", x),
+            SettingValue::AncrGestureLoop(x) => write!(f, "ANC gesture loop: {}", x),
+            SettingValue::CurrentAncrState(x) => write!(f, "ANC state: {}", x),
+            SettingValue::OttsMode(x) => write!(f, "OTTS mode: {}", x),
+            SettingValue::VolumeEqEnable(x) => write!(f, "volume EQ enabled: {}", x),
+            SettingValue::CurrentUserEq(x) => write!(f, "user EQ: {}", x),
+            SettingValue::VolumeAsymmetry(x) => write!(f, "volume asymmetry: {}", x),
+        }
+    }
+}
+
 impl From<types::setting_value::ValueOneof> for SettingValue {
     fn from(value: crate::protocol::types::setting_value::ValueOneof) -> Self {
         use types::setting_value::ValueOneof;
@@ -115,8 +141,147 @@ impl From<SettingValue> for types::setting_value::ValueOneof {
     }
 }
 
+impl types::SettingsRsp {
+    /// Settings value carried by this response, if any.
+    pub fn value(&self) -> Option<SettingValue> {
+        let types::settings_rsp::ValueOneof::Value(value) = self.value_oneof.as_ref()?;
+        value.value_oneof.clone().map(SettingValue::from)
+    }
+
+    /// ANC state carried by this response, if it is a `CurrentAncrState`
+    /// value.
+    pub fn anc_state(&self) -> Option<AncState> {
+        match self.value()? {
+            SettingValue::CurrentAncrState(state) => Some(state),
+            _ => None,
+        }
+    }
+
+    /// Settings whose values differ between this and the given response.
+    ///
+    /// Each response carries the value of a single setting. If both carry a
+    /// value of the same setting, it is returned if the values differ.
+    /// Otherwise, the settings of both responses are returned, as each is
+    /// missing from the other response.
+    pub fn changed_fields(&self, other: &types::SettingsRsp) -> Vec<SettingId> {
+        match (self.value(), other.value()) {
+            (Some(a), Some(b)) if a.id() == b.id() => {
+                if a != b { vec![a.id()] } else { Vec::new() }
+            },
+            (a, b) => a.into_iter().chain(b).map(|value| value.id()).collect(),
+        }
+    }
+}
+
+impl From<SettingValue> for types::WriteSettingMsg {
+    fn from(value: SettingValue) -> Self {
+        let value = types::SettingValue {
+            value_oneof: Some(value.into()),
+        };
+
+        types::WriteSettingMsg {
+            value_oneof: Some(types::write_setting_msg::ValueOneof::Setting(value)),
+        }
+    }
+}
+
+
+/// Builder for writing multiple settings at once.
+///
+/// The protocol has no request for changing multiple settings at once: each
+/// `WriteSetting` call carries exactly one value. The builder therefore
+/// produces one [`WriteSettingMsg`](types::WriteSettingMsg) per setting, in
+/// the order the settings have been first set. Setting the same value twice
+/// replaces the earlier one, so each setting is written at most once. Apart
+/// from that, we are not aware of any combinations that are rejected by the
+/// device.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SettingsBuilder {
+    values: Vec<SettingValue>,
+}
+
+impl SettingsBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn value(mut self, value: SettingValue) -> Self {
+        match self.values.iter_mut().find(|v| v.id() == value.id()) {
+            Some(v) => *v = value,
+            None => self.values.push(value),
+        }
+        self
+    }
+
+    pub fn anc_state(self, state: AncState) -> Self {
+        self.value(SettingValue::CurrentAncrState(state))
+    }
+
+    pub fn ancr_gesture_loop(self, gesture_loop: AncrGestureLoop) -> Self {
+        self.value(SettingValue::AncrGestureLoop(gesture_loop))
+    }
+
+    pub fn gesture_control(self, control: GestureControl) -> Self {
+        self.value(SettingValue::GestureControl(control))
+    }
+
+    pub fn ohd_enable(self, enabled: bool) -> Self {
+        self.value(SettingValue::OhdEnable(enabled))
+    }
+
+    pub fn gesture_enable(self, enabled: bool) -> Self {
+        self.value(SettingValue::GestureEnable(enabled))
+    }
+
+    pub fn multipoint_enable(self, enabled: bool) -> Self {
+        self.value(SettingValue::MultipointEnable(enabled))
+    }
+
+    pub fn volume_eq_enable(self, enabled: bool) -> Self {
+        self.value(SettingValue::VolumeEqEnable(enabled))
+    }
+
+    pub fn user_eq(self, bands: EqBands) -> Self {
+        self.value(SettingValue::CurrentUserEq(bands))
+    }
+
+    pub fn volume_asymmetry(self, value: VolumeAsymmetry) -> Self {
+        self.value(SettingValue::VolumeAsymmetry(value))
+    }
+
+    /// Build the messages for writing the settings.
+    ///
+    /// Fails with [`Status::InvalidArgument`](crate::pwrpc::Status) if no
+    /// setting has been set or if an ANC gesture loop with less than two
+    /// states is set.
+    pub fn build(self) -> Result<Vec<types::WriteSettingMsg>, Error> {
+        Ok(self.build_values()?.into_iter().map(Into::into).collect())
+    }
+
+    /// Validate the settings and return them as values, in the order they
+    /// are written. Fails in the same cases as [`build`](Self::build).
+    pub fn build_values(self) -> Result<Vec<SettingValue>, Error> {
+        if self.values.is_empty() {
+            return Err(Error::invalid_argument("no setting to write"));
+        }
+
+        for value in &self.values {
+            if let SettingValue::AncrGestureLoop(gesture_loop) = value {
+                if !gesture_loop.is_valid() {
+                    return Err(Error::invalid_argument(
+                        "ANC gesture loop must contain at least two states"
+                    ));
+                }
+            }
+        }
+
+        Ok(self.values)
+    }
+}
+
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct GestureControl {
     pub left: RegularActionTarget,
     pub right: RegularActionTarget,
@@ -172,6 +337,20 @@ impl Default for GestureControl {
     }
 }
 
+impl GestureControl {
+    /// Return the changes from this gesture control to the given one.
+    ///
+    /// Only the hold gesture can be configured (see [`GestureControl`]), so
+    /// changes are reported per bud.
+    pub fn diff(&self, other: &GestureControl) -> Vec<GestureControlDiff> {
+        [(Bud::Left, self.left, other.left), (Bud::Right, self.right, other.right)]
+            .into_iter()
+            .filter(|(_, old, new)| old != new)
+            .map(|(bud, old, new)| GestureControlDiff { bud, old, new })
+            .collect()
+    }
+}
+
 impl std::fmt::Display for GestureControl {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "left: {}, right: {}", self.left, self.right)
@@ -179,8 +358,50 @@ impl std::fmt::Display for GestureControl {
 }
 
 
+/// Left or right bud.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "snake_case"))]
+pub enum Bud {
+    Left,
+    Right,
+}
+
+impl Bud {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Bud::Left => "left",
+            Bud::Right => "right",
+        }
+    }
+}
+
+impl std::fmt::Display for Bud {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+
+/// Changed hold gesture action of a single bud, see [`GestureControl::diff`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GestureControlDiff {
+    pub bud: Bud,
+    pub old: RegularActionTarget,
+    pub new: RegularActionTarget,
+}
+
+impl std::fmt::Display for GestureControlDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} -> {}", self.bud, self.old, self.new)
+    }
+}
+
+
+#[non_exhaustive]
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum RegularActionTarget {
     CheckNotifications = 1,
     PreviousTrackRepeat = 2,
@@ -223,6 +444,7 @@ impl std::fmt::Display for RegularActionTarget {
 
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct AncrGestureLoop {
     pub active: bool,
     pub off: bool,
@@ -285,8 +507,10 @@ impl std::fmt::Display for AncrGestureLoop {
 }
 
 
+#[non_exhaustive]
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum AncState {
     Off = 1,
     Active = 2,
@@ -307,6 +531,7 @@ impl AncState {
     }
 }
 
+#[allow(clippy::derivable_impls)]    // deriving conflicts with num_enum's catch_all
 impl Default for AncState {
     fn default() -> Self {
         AncState::Off
@@ -326,6 +551,7 @@ impl std::fmt::Display for AncState {
 
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct EqBands {
     low_bass: f32,
     bass: f32,
@@ -436,6 +662,7 @@ impl std::fmt::Display for EqBands {
 
 
 #[derive(Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
 pub struct VolumeAsymmetry {
     value: i32,
 }
@@ -747,4 +974,88 @@ mod test {
             assert_eq!(VolumeAsymmetry::from_raw(i).raw(), i)
         }
     }
+
+    #[test]
+    fn test_gesture_control_diff() {
+        let a = GestureControl::default();
+        let b = GestureControl { right: RegularActionTarget::AssistantQuery, ..a };
+
+        assert!(a.diff(&a).is_empty());
+
+        let diff = a.diff(&b);
+        assert_eq!(diff, [GestureControlDiff {
+            bud: Bud::Right,
+            old: RegularActionTarget::AncControl,
+            new: RegularActionTarget::AssistantQuery,
+        }]);
+        assert_eq!(diff[0].to_string(), "right: anc -> assistant");
+
+        let c = GestureControl { left: RegularActionTarget::Unknown(-1), ..b };
+        let buds: Vec<_> = a.diff(&c).iter().map(|d| d.bud).collect();
+        assert_eq!(buds, [Bud::Left, Bud::Right]);
+    }
+
+    #[test]
+    fn test_settings_rsp_anc_state() {
+        let rsp = |value| types::SettingsRsp {
+            value_oneof: Some(types::settings_rsp::ValueOneof::Value(types::SettingValue {
+                value_oneof: Some(value),
+            })),
+        };
+
+        let value = types::setting_value::ValueOneof::CurrentAncrState(3);
+        assert_eq!(rsp(value).anc_state(), Some(AncState::Aware));
+
+        let value = types::setting_value::ValueOneof::MultipointEnable(true);
+        assert_eq!(rsp(value).anc_state(), None);
+
+        assert_eq!(types::SettingsRsp::default().anc_state(), None);
+    }
+
+    #[test]
+    fn test_settings_rsp_changed_fields() {
+        let rsp = |value: SettingValue| types::SettingsRsp {
+            value_oneof: Some(types::settings_rsp::ValueOneof::Value(types::SettingValue {
+                value_oneof: Some(value.into()),
+            })),
+        };
+
+        let off = rsp(SettingValue::CurrentAncrState(AncState::Off));
+        let aware = rsp(SettingValue::CurrentAncrState(AncState::Aware));
+        let ohd = rsp(SettingValue::OhdEnable(true));
+        let none = types::SettingsRsp::default();
+
+        assert_eq!(off.changed_fields(&off), []);
+        assert_eq!(off.changed_fields(&aware), [SettingId::CurrentAncrState]);
+        assert_eq!(off.changed_fields(&ohd), [SettingId::CurrentAncrState, SettingId::OhdEnable]);
+        assert_eq!(none.changed_fields(&ohd), [SettingId::OhdEnable]);
+        assert_eq!(none.changed_fields(&none), []);
+    }
+
+    #[test]
+    fn test_settings_builder() {
+        let gesture_loop = AncrGestureLoop { active: true, off: false, aware: true };
+
+        let msgs = SettingsBuilder::new()
+            .ohd_enable(false)
+            .anc_state(AncState::Off)
+            .ancr_gesture_loop(gesture_loop)
+            .anc_state(AncState::Active)
+            .build()
+            .unwrap();
+
+        let expected: Vec<types::WriteSettingMsg> = vec![
+            SettingValue::OhdEnable(false).into(),
+            SettingValue::CurrentAncrState(AncState::Active).into(),
+            SettingValue::AncrGestureLoop(gesture_loop).into(),
+        ];
+        assert_eq!(msgs, expected);
+
+        let err = SettingsBuilder::new().build().unwrap_err();
+        assert_eq!(err.code(), crate::pwrpc::Status::InvalidArgument);
+
+        let gesture_loop = AncrGestureLoop { active: true, off: false, aware: false };
+        let err = SettingsBuilder::new().ancr_gesture_loop(gesture_loop).build().unwrap_err();
+        assert_eq!(err.code(), crate::pwrpc::Status::InvalidArgument);
+    }
 }